@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/network/schema/codec.rs"]
+mod codec;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pong) = codec::decode_unconnected_pong(data) {
+        let _ = codec::decode_bedrock_status(&pong.server_id);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/network/schema/codec.rs"]
+mod codec;
+#[allow(dead_code)]
+#[path = "../../src/network/schema/forge.rs"]
+mod forge;
+
+fuzz_target!(|data: &str| {
+    let _ = forge::decode_forge_data(data);
+});
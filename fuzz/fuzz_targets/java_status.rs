@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/network/schema/codec.rs"]
+mod codec;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = codec::decode_status_response(data);
+    let _ = codec::decode_pong_response(data);
+});
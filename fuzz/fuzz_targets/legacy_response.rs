@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/network/schema/codec.rs"]
+mod codec;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = codec::decode_legacy_string(data);
+});
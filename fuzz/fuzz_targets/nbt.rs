@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/network/schema/codec.rs"]
+mod codec;
+#[allow(dead_code)]
+#[path = "../../src/network/schema/nbt.rs"]
+mod nbt;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((name, root)) = nbt::decode_nbt(data) {
+        // Whatever decodes must survive a round trip, compared as bytes since NaN != NaN
+        let encoded = nbt::encode_nbt(&name, &root);
+        let (name, root) = nbt::decode_nbt(&encoded).expect("re-encoded NBT must decode");
+        assert_eq!(nbt::encode_nbt(&name, &root), encoded);
+    }
+});
@@ -0,0 +1,145 @@
+use crate::analyze::StatusPayload;
+use crate::analyze::motd::plain_motd;
+
+const CELL_WIDTH: usize = 32;
+
+fn cell(text: &str) -> String {
+    if text.chars().count() > CELL_WIDTH {
+        let cut = text.chars().take(CELL_WIDTH - 3).collect::<String>();
+        format!("{}...", cut)
+    } else {
+        text.to_string()
+    }
+}
+
+fn row(field: &str, java: &str, bedrock: &str, matched: Option<bool>) {
+    let matched = match matched {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "-",
+    };
+    log::info!(
+        "  {:10} {:w$} {:w$} {}",
+        field,
+        cell(java),
+        cell(bedrock),
+        matched,
+        w = CELL_WIDTH
+    );
+}
+
+fn or_unknown<T: ToString>(value: Option<T>) -> String {
+    value.map_or("<UNKNOWN>".to_string(), |v| v.to_string())
+}
+
+/// Non-empty MOTD lines without formatting, bridges often reflow the whitespace
+fn motd_lines(payload: &StatusPayload) -> Vec<String> {
+    payload
+        .motd
+        .as_ref()
+        .map(plain_motd)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+fn is_geyser(payload: &StatusPayload) -> bool {
+    let version = payload.version_name.as_deref().unwrap_or("");
+    let motd = payload.motd.as_ref().map(plain_motd).unwrap_or_default();
+    version.to_lowercase().contains("geyser") || motd.to_lowercase().contains("geyser")
+}
+
+/// Side-by-side summary of a Java and a Bedrock answer from the same host
+pub fn crossplay_report(java: &StatusPayload, bedrock: &StatusPayload) {
+    let java_motd = motd_lines(java);
+    let bedrock_motd = motd_lines(bedrock);
+    let same_players = java.player_count.is_some() && java.player_count == bedrock.player_count;
+    let same_max = java.max_players.is_some() && java.max_players == bedrock.max_players;
+    let same_motd = !java_motd.is_empty() && java_motd == bedrock_motd;
+    let same_first_line = !java_motd.is_empty() && java_motd.first() == bedrock_motd.first();
+    let geyser = is_geyser(bedrock);
+
+    log::info!("Crossplay report:");
+    log::info!(
+        "  {:10} {:w$} {:w$} Match",
+        "",
+        format!("{:?}", java.mode),
+        "BEDROCK",
+        w = CELL_WIDTH
+    );
+    row(
+        "Version",
+        &or_unknown(java.version_name.as_ref()),
+        &or_unknown(bedrock.version_name.as_ref()),
+        None,
+    );
+    row(
+        "Protocol",
+        &or_unknown(java.protocol),
+        &or_unknown(bedrock.protocol),
+        None,
+    );
+    row(
+        "Online",
+        &or_unknown(java.player_count),
+        &or_unknown(bedrock.player_count),
+        Some(same_players),
+    );
+    row(
+        "Max",
+        &or_unknown(java.max_players),
+        &or_unknown(bedrock.max_players),
+        Some(same_max),
+    );
+    let lines = java_motd.len().max(bedrock_motd.len()).max(1);
+    for index in 0..lines {
+        let java_line = java_motd.get(index).map_or("", |l| l.as_str());
+        let bedrock_line = bedrock_motd.get(index).map_or("", |l| l.as_str());
+        row(
+            if index == 0 { "MOTD" } else { "" },
+            java_line,
+            bedrock_line,
+            Some(java_line == bedrock_line),
+        );
+    }
+    row(
+        "Ping",
+        &format!("{}ms", java.ping),
+        &format!("{}ms", bedrock.ping),
+        None,
+    );
+
+    let mut score = 0;
+    let mut evidence = vec![];
+    if geyser {
+        score += 2;
+        evidence.push("Bedrock answer mentions Geyser");
+    }
+    if same_players && same_max {
+        score += 1;
+        evidence.push("player counts are identical");
+    } else if java.player_count.is_some() && bedrock.player_count.is_some() && !same_players {
+        score -= 1;
+        evidence.push("online player counts differ");
+    }
+    if same_motd {
+        score += 1;
+        evidence.push("MOTD text matches");
+    } else if same_first_line {
+        score += 1;
+        evidence.push("first MOTD line matches");
+    }
+
+    let conclusion = match score {
+        2.. => "Bedrock endpoint looks like a Geyser bridge to the Java server",
+        1 => "Bedrock endpoint may be bridged to the Java server",
+        _ => "Bedrock endpoint appears to be a separate server",
+    };
+    if evidence.is_empty() {
+        log::info!("{}", conclusion);
+    } else {
+        log::info!("{}: {}", conclusion, evidence.join(", "));
+    }
+}
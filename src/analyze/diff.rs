@@ -0,0 +1,168 @@
+use crate::analyze::favicon::favicon_hash;
+use crate::analyze::motd::plain_motd;
+use crate::analyze::{MotdInfo, StatusPayload};
+#[cfg(feature = "analyze-forge-info")]
+use crate::network::schema::forge::decode_forge_data;
+use clap::Args;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Snapshot saved with `--snapshot` to compare from
+    pub old: PathBuf,
+    /// Snapshot to compare to, or an address to query now when no such file exists
+    pub new: String,
+}
+
+fn compare<T: PartialEq + Display>(
+    changes: &mut Vec<String>,
+    what: &str,
+    old: Option<T>,
+    new: Option<T>,
+) {
+    if old == new {
+        return;
+    }
+    let show = |value: Option<T>| value.map_or("<none>".to_string(), |v| v.to_string());
+    changes.push(format!("{}: {} -> {}", what, show(old), show(new)));
+}
+
+/// MOTD as the server sent it, so a change of colors alone is noticed
+fn raw_motd(motd: &MotdInfo) -> String {
+    match motd {
+        MotdInfo::String(motd) => motd.clone(),
+        MotdInfo::Component(component) => component.to_string(),
+    }
+}
+
+#[cfg(feature = "analyze-forge-info")]
+fn encoded_forge_mods(forge: &Value) -> Option<BTreeMap<String, String>> {
+    let data = decode_forge_data(forge["d"].as_str()?).ok()?;
+    Some(
+        data.mods
+            .into_iter()
+            .map(|m| (m.name, m.version.unwrap_or_default()))
+            .collect(),
+    )
+}
+
+#[cfg(not(feature = "analyze-forge-info"))]
+fn encoded_forge_mods(_: &Value) -> Option<BTreeMap<String, String>> {
+    None
+}
+
+/// Mod versions by mod id, from whichever Forge format the server used
+fn forge_mods(payload: &StatusPayload) -> Option<BTreeMap<String, String>> {
+    let extra = payload.full_extra.as_ref()?;
+    let forge = &extra["forgeData"];
+    if let Some(mods) = encoded_forge_mods(forge) {
+        return Some(mods);
+    }
+    let (mods, id, version) = match forge["mods"].as_array() {
+        Some(mods) => (mods, "modId", "modmarker"),
+        // FML 1 servers before 1.13
+        None => (extra["modinfo"]["modList"].as_array()?, "modid", "version"),
+    };
+    Some(
+        mods.iter()
+            .map(|m| {
+                let name = m[id].as_str().unwrap_or_default().to_string();
+                (name, m[version].as_str().unwrap_or_default().to_string())
+            })
+            .collect(),
+    )
+}
+
+fn compare_mods(changes: &mut Vec<String>, old: &StatusPayload, new: &StatusPayload) {
+    let (old, new) = (forge_mods(old), forge_mods(new));
+    if old.is_none() && new.is_none() {
+        return;
+    }
+    let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+    for (name, version) in &new {
+        match old.get(name) {
+            None => changes.push(format!("Mod added: {} ({})", name, version)),
+            Some(before) if before != version => {
+                changes.push(format!("Mod {}: {} -> {}", name, before, version))
+            }
+            _ => {}
+        }
+    }
+    for (name, version) in &old {
+        if !new.contains_key(name) {
+            changes.push(format!("Mod removed: {} ({})", name, version));
+        }
+    }
+}
+
+/// Top-level keys with a value, fields taken out into the payload are left as null
+fn extra_keys(payload: &StatusPayload) -> Vec<&str> {
+    match payload.full_extra.as_ref() {
+        Some(Value::Object(map)) => map
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, _)| key.as_str())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Everything that differs between two statuses, one line each, ping aside
+pub fn diff_status(old: &StatusPayload, new: &StatusPayload) -> Vec<String> {
+    let mut changes = vec![];
+    compare(
+        &mut changes,
+        "Mode",
+        Some(format!("{:?}", old.mode)),
+        Some(format!("{:?}", new.mode)),
+    );
+    compare(
+        &mut changes,
+        "Version",
+        old.version_name.as_ref(),
+        new.version_name.as_ref(),
+    );
+    compare(&mut changes, "Protocol", old.protocol, new.protocol);
+
+    let text = |p: &StatusPayload| p.motd.as_ref().map(|m| format!("{:?}", plain_motd(m)));
+    let (old_text, new_text) = (text(old), text(new));
+    if old_text == new_text {
+        if old.motd.as_ref().map(raw_motd) != new.motd.as_ref().map(raw_motd) {
+            changes.push("MOTD style changed".to_string());
+        }
+    } else {
+        compare(&mut changes, "MOTD", old_text, new_text);
+    }
+
+    compare(
+        &mut changes,
+        "Players online",
+        old.player_count,
+        new.player_count,
+    );
+    compare(
+        &mut changes,
+        "Max players",
+        old.max_players,
+        new.max_players,
+    );
+    compare(
+        &mut changes,
+        "Favicon hash",
+        old.favicon.as_deref().map(favicon_hash),
+        new.favicon.as_deref().map(favicon_hash),
+    );
+    compare_mods(&mut changes, old, new);
+
+    let (old_keys, new_keys) = (extra_keys(old), extra_keys(new));
+    for key in new_keys.iter().filter(|key| !old_keys.contains(key)) {
+        changes.push(format!("Extra key added: {}", key));
+    }
+    for key in old_keys.iter().filter(|key| !new_keys.contains(key)) {
+        changes.push(format!("Extra key removed: {}", key));
+    }
+    changes
+}
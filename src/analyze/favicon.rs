@@ -1,7 +1,10 @@
 use crate::analyze::{Analyzer, StatusPayload};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use clap::Args;
+use colored::Colorize;
+use png::{ColorType, Decoder, Transformations};
+use std::io::Cursor;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
@@ -32,9 +35,8 @@ impl Analyzer for Favicon<'_> {
 
     async fn analyze(&self, payload: &StatusPayload) {
         match do_favicon_output(
-            &payload.favicon.as_ref().expect("No favicon provided"),
-            &self
-                .args
+            payload.favicon.as_ref().expect("No favicon provided"),
+            self.args
                 .favicon
                 .as_ref()
                 .expect("No favicon output provided"),
@@ -45,6 +47,72 @@ impl Analyzer for Favicon<'_> {
             Err(e) => log::error!("Favicon output error: {}", e),
         }
     }
+
+    fn writes_output(&self) -> bool {
+        true
+    }
+}
+
+/// FNV-1a of the favicon data, short enough to compare by eye
+pub fn favicon_hash(favicon: &str) -> String {
+    let hash = favicon.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+type Pixel = Option<(u8, u8, u8)>;
+
+/// Pixels with at least half opacity, transparent ones are left to the terminal background
+fn decode_pixels(favicon: &str) -> Result<(usize, usize, Vec<Pixel>)> {
+    let data_url = data_url::DataUrl::process(favicon)?;
+    let bytes = data_url.decode_to_vec()?.0;
+    let mut decoder = Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![
+        0;
+        reader
+            .output_buffer_size()
+            .ok_or(anyhow!("Favicon is too large"))?
+    ];
+    let info = reader.next_frame(&mut buf)?;
+    let pixels = buf[..info.buffer_size()]
+        .chunks_exact(info.color_type.samples())
+        .map(|p| match info.color_type {
+            ColorType::Rgba => (p[3] >= 128).then_some((p[0], p[1], p[2])),
+            ColorType::GrayscaleAlpha => (p[1] >= 128).then_some((p[0], p[0], p[0])),
+            ColorType::Grayscale => Some((p[0], p[0], p[0])),
+            _ => Some((p[0], p[1], p[2])),
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+/// Favicon drawn with half blocks in true colors, two pixel rows per line and at most `columns` wide
+pub fn favicon_preview(favicon: &str, columns: usize) -> Result<Vec<String>> {
+    let (width, height, pixels) = decode_pixels(favicon)?;
+    let step = width.div_ceil(columns.max(1)).max(1);
+    let pixel = |x: usize, y: usize| pixels.get(y * width + x).copied().flatten();
+    let mut lines = vec![];
+    for y in (0..height).step_by(step * 2) {
+        let mut line = String::new();
+        for x in (0..width).step_by(step) {
+            let top = pixel(x, y);
+            let bottom = (y + step < height).then(|| pixel(x, y + step)).flatten();
+            let cell = match (top, bottom) {
+                (None, None) => " ".normal(),
+                (Some((r, g, b)), None) => "▀".truecolor(r, g, b),
+                (None, Some((r, g, b))) => "▄".truecolor(r, g, b),
+                (Some((r, g, b)), Some((br, bg, bb))) => {
+                    "▀".truecolor(r, g, b).on_truecolor(br, bg, bb)
+                }
+            };
+            line.push_str(&cell.to_string());
+        }
+        lines.push(line);
+    }
+    Ok(lines)
 }
 
 impl Favicon<'_> {
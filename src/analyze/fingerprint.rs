@@ -0,0 +1,258 @@
+use crate::analyze::{Analyzer, MotdInfo, StatusPayload};
+use crate::mode::QueryMode::BEDROCK;
+use async_trait::async_trait;
+use regex_lite::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+static VERSION_RANGE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\d+\.\d+(?:\.[\dx]+)?\s*-\s*\d+\.\d+").expect("Compile regex failed!")
+});
+static PLAIN_VERSION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d+\.\d+(?:\.\d+)?$").expect("Compile regex failed!"));
+
+const PROXIES: [&str; 3] = ["Velocity", "BungeeCord", "Waterfall"];
+const VANILLA_BASED: [&str; 4] = ["Vanilla", "Paper", "Spigot", "Purpur"];
+
+/// Known markers in version names, checked case-insensitively
+const VERSION_MARKERS: [(&str, &str, u32); 11] = [
+    ("velocity", "Velocity", 5),
+    ("bungeecord", "BungeeCord", 5),
+    ("waterfall", "Waterfall", 5),
+    ("purpur", "Purpur", 5),
+    ("folia", "Folia", 5),
+    ("paper", "Paper", 4),
+    ("spigot", "Spigot", 4),
+    ("craftbukkit", "Spigot", 3),
+    ("neoforge", "Forge", 4),
+    ("forge", "Forge", 3),
+    ("fabric", "Fabric", 3),
+];
+
+/// Known markers in Bedrock MOTD lines, checked case-insensitively
+const BEDROCK_MARKERS: [(&str, &str, u32); 4] = [
+    ("geyser", "Geyser", 5),
+    ("pocketmine", "PocketMine-MP", 5),
+    ("nukkit", "Nukkit", 5),
+    ("bedrock level", "Bedrock Dedicated Server", 3),
+];
+
+#[derive(Default)]
+struct Evidence {
+    scores: Vec<(&'static str, u32)>,
+    total: u32,
+    reasons: Vec<String>,
+}
+
+impl Evidence {
+    fn add(&mut self, candidates: &[&'static str], weight: u32, reason: String) {
+        for candidate in candidates {
+            match self.scores.iter_mut().find(|(name, _)| name == candidate) {
+                Some((_, score)) => *score += weight,
+                None => self.scores.push((candidate, weight)),
+            }
+        }
+        self.total += weight;
+        self.reasons
+            .push(format!("{} -> {}", reason, candidates.join("/")));
+    }
+
+    fn best(&self) -> Option<(&'static str, u32)> {
+        // Earlier candidates win ties, so the strongest signal keeps priority
+        self.scores.iter().fold(
+            None,
+            |best: Option<(&str, u32)>, &(name, score)| match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((name, score)),
+            },
+        )
+    }
+}
+
+fn java_evidence(payload: &StatusPayload, evidence: &mut Evidence) {
+    if let Some(name) = payload.version_name.as_ref() {
+        let lower = name.to_lowercase();
+        if let Some((marker, software, weight)) = VERSION_MARKERS
+            .iter()
+            .find(|(marker, _, _)| lower.contains(marker))
+        {
+            evidence.add(
+                &[software],
+                *weight,
+                format!("Version name \"{}\" contains \"{}\"", name, marker),
+            );
+        }
+        if VERSION_RANGE_REGEX.is_match(name) {
+            evidence.add(
+                &PROXIES,
+                2,
+                format!("Version name \"{}\" advertises a version range", name),
+            );
+        } else if PLAIN_VERSION_REGEX.is_match(name) {
+            evidence.add(
+                &["Vanilla"],
+                2,
+                format!("Version name \"{}\" is a bare version", name),
+            );
+        }
+    }
+
+    let Some(extra) = payload.full_extra.as_ref().and_then(|x| x.as_object()) else {
+        return;
+    };
+
+    if extra.contains_key("forgeData") || extra.contains_key("modinfo") {
+        evidence.add(&["Forge"], 4, "Status carries Forge mod data".to_string());
+    }
+    if extra.contains_key("enforcesSecureChat") {
+        evidence.add(
+            &VANILLA_BASED,
+            1,
+            "Status reports enforcesSecureChat".to_string(),
+        );
+    }
+    if extra.contains_key("preventsChatReports") {
+        evidence.add(
+            &["Fabric", "Paper"],
+            1,
+            "Status reports preventsChatReports from a chat report blocker".to_string(),
+        );
+    }
+
+    // Vanilla writes its codec order, proxies serialize their own ping object
+    match extra.keys().next().map(|k| k.as_str()) {
+        Some("version") => evidence.add(
+            &PROXIES,
+            2,
+            "Status JSON starts with \"version\"".to_string(),
+        ),
+        Some("description") => evidence.add(
+            &VANILLA_BASED,
+            1,
+            "Status JSON starts with \"description\"".to_string(),
+        ),
+        _ => {}
+    }
+
+    if let Some(probes) = extra.get("handshake_probe").and_then(|x| x.as_array()) {
+        probe_evidence(payload, probes, evidence);
+    }
+}
+
+fn probe_evidence(payload: &StatusPayload, probes: &[Value], evidence: &mut Evidence) {
+    let mut echoed = vec![];
+    let mut fixed = 0;
+    let mut failed = vec![];
+    for probe in probes {
+        let Some(sent) = probe["sent"].as_i64() else {
+            continue;
+        };
+        match probe["protocol"].as_i64() {
+            Some(protocol) if protocol == sent => echoed.push(sent),
+            Some(protocol) if Some(protocol) == payload.protocol => fixed += 1,
+            Some(_) => {}
+            None => failed.push(sent),
+        }
+    }
+    if !echoed.is_empty() {
+        evidence.add(
+            &PROXIES,
+            2,
+            format!("Server echoed unusual client protocols {:?}", echoed),
+        );
+    }
+    if fixed == probes.len() && fixed > 0 {
+        evidence.add(
+            &VANILLA_BASED,
+            1,
+            "Server reported the same protocol for unusual handshakes".to_string(),
+        );
+    }
+    if !failed.is_empty() {
+        evidence.add(
+            &["BungeeCord", "Waterfall"],
+            1,
+            format!("Server refused unusual client protocols {:?}", failed),
+        );
+    }
+}
+
+fn bedrock_evidence(payload: &StatusPayload, evidence: &mut Evidence) {
+    if let Some(MotdInfo::String(motd)) = payload.motd.as_ref() {
+        let lower = motd.to_lowercase();
+        if let Some((marker, software, weight)) = BEDROCK_MARKERS
+            .iter()
+            .find(|(marker, _, _)| lower.contains(marker))
+        {
+            evidence.add(
+                &[software],
+                *weight,
+                format!("MOTD contains \"{}\"", marker),
+            );
+        }
+    }
+
+    let Some(extra) = payload.full_extra.as_ref() else {
+        return;
+    };
+    if extra["edition"].as_str() == Some("MCEE") {
+        evidence.add(
+            &["Education Edition"],
+            5,
+            "MOTD edition is MCEE".to_string(),
+        );
+    }
+    if let Some(count) = extra["field_count"].as_u64() {
+        if count >= 12 {
+            evidence.add(
+                &["Bedrock Dedicated Server", "Geyser"],
+                1,
+                format!("Pong carries {} fields including ports", count),
+            );
+        } else {
+            evidence.add(
+                &["PocketMine-MP", "Nukkit"],
+                1,
+                format!("Pong carries only {} fields", count),
+            );
+        }
+    }
+    if extra["trailing_separator"].as_bool() == Some(false) {
+        evidence.add(
+            &["PocketMine-MP", "Nukkit"],
+            1,
+            "Pong has no trailing separator".to_string(),
+        );
+    }
+}
+
+pub struct Fingerprint;
+
+#[async_trait]
+impl Analyzer for Fingerprint {
+    fn enabled(&self, _payload: &StatusPayload) -> bool {
+        true
+    }
+
+    async fn analyze(&self, payload: &StatusPayload) {
+        let mut evidence = Evidence::default();
+        if payload.mode == BEDROCK {
+            bedrock_evidence(payload, &mut evidence);
+        } else {
+            java_evidence(payload, &mut evidence);
+        }
+
+        let Some((software, score)) = evidence.best() else {
+            log::info!("Server Software: <UNKNOWN>");
+            return;
+        };
+        log::info!(
+            "Server Software: {} ({}% confidence)",
+            software,
+            score * 100 / evidence.total
+        );
+        for reason in evidence.reasons.iter() {
+            log::info!("  {}", reason);
+        }
+    }
+}
@@ -1,8 +1,7 @@
 use crate::analyze::{Analyzer, StatusPayload};
-use crate::network::schema::{read_string, read_var_int_buf};
-use anyhow::{Result, anyhow};
+use crate::network::schema::forge::{ForgeChannel, decode_forge_data};
+use anyhow::Result;
 use async_trait::async_trait;
-use bytes::{Buf, BufMut, BytesMut};
 use clap::Args;
 
 #[derive(Args, Debug)]
@@ -16,86 +15,37 @@ pub struct ForgeInfo<'a> {
     args: &'a ForgeInfoArgs,
 }
 
-async fn try_analyze_encoded(data: &str, display_channels: bool) -> Result<()> {
-    let chars = data.encode_utf16().collect::<Vec<u16>>();
-    if chars.len() < 2 {
-        return Err(anyhow!("ForgeData too short"));
+fn log_channel(channel: &ForgeChannel) {
+    if channel.required {
+        log::info!("  Channel* {} ({})", channel.path, channel.version);
+    } else {
+        log::info!("  Channel  {} ({})", channel.path, channel.version);
     }
+}
 
-    let buffer_len = chars[0] as u32 | (chars[1] as u32) << 15;
-    let mut buffer = BytesMut::with_capacity(buffer_len as usize);
-    let mut bits_in_buf = 0;
-    let mut buf = 0;
-    for char in chars[2..].iter() {
-        while bits_in_buf >= 8 {
-            buffer.put_u8((buf & 0xFF) as u8);
-            buf >>= 8;
-            bits_in_buf -= 8;
-        }
-        buf |= ((*char as u32) & 32767) << bits_in_buf;
-        bits_in_buf += 15;
-    }
-    while bits_in_buf > 0 {
-        buffer.put_u8((buf & 0xFF) as u8);
-        buf >>= 8;
-        bits_in_buf -= 8;
-    }
-    log::trace!("ForgeData: {:?}", String::from_utf8(buffer.to_vec()));
+async fn try_analyze_encoded(data: &str, display_channels: bool) -> Result<()> {
+    let forge_data = decode_forge_data(data)?;
 
-    if buffer.try_get_u8()? != 0 {
+    if forge_data.truncated {
         log::info!("Server truncated mod information");
     }
 
-    let size = buffer.try_get_u16()?;
-    for _ in 0..size {
-        let flag = read_var_int_buf(&mut buffer)?;
-        let ch_size = flag >> 1 & (!(1 << 31));
-        let ignore_server_only = flag & 1 != 0;
-        let name = read_string(&mut buffer)?;
-        let version = if ignore_server_only {
-            "<UNCHECKED>".to_string()
-        } else {
-            read_string(&mut buffer)?
-        };
-        if version == "" {
-            log::info!("Mod: {}", name);
-        } else {
-            log::info!("Mod: {} ({})", name, version);
+    for mod_data in forge_data.mods.iter() {
+        match mod_data.version.as_deref() {
+            Some("") => log::info!("Mod: {}", mod_data.name),
+            Some(version) => log::info!("Mod: {} ({})", mod_data.name, version),
+            None => log::info!("Mod: {} (<UNCHECKED>)", mod_data.name),
         }
-
-        for _ in 0..ch_size {
-            let path = read_string(&mut buffer)?;
-            let ver = read_string(&mut buffer)?;
-            let required = buffer.try_get_u8()? != 0;
-            if !display_channels {
-                continue;
-            }
-            if required {
-                log::info!("  Channel* {} ({})", path, ver);
-            } else {
-                log::info!("  Channel  {} ({})", path, ver);
-            }
+        if display_channels {
+            mod_data.channels.iter().for_each(log_channel);
         }
     }
 
-    if !display_channels {
-        return Ok(());
-    }
-
-    let non_mod_channels = read_var_int_buf(&mut buffer)?;
-    if non_mod_channels == 0 {
+    if !display_channels || forge_data.non_mod_channels.is_empty() {
         return Ok(());
     }
     log::info!("Non-mod channels:");
-    for _ in 0..non_mod_channels {
-        let path = read_string(&mut buffer)?;
-        let ver = read_string(&mut buffer)?;
-        if buffer.try_get_u8()? != 0 {
-            log::info!("  Channel* {} ({})", path, ver);
-        } else {
-            log::info!("  Channel  {} ({})", path, ver);
-        }
-    }
+    forge_data.non_mod_channels.iter().for_each(log_channel);
 
     Ok(())
 }
@@ -106,8 +56,7 @@ impl Analyzer for ForgeInfo<'_> {
         payload
             .full_extra
             .as_ref()
-            .map(|m| m["forgeData"].as_object())
-            .flatten()
+            .and_then(|m| m["forgeData"].as_object())
             .is_some()
     }
 
@@ -121,10 +70,9 @@ impl Analyzer for ForgeInfo<'_> {
                 .unwrap_or("<unknown version>".to_string())
         );
         if let Some(data) = forge_data["d"].as_str() {
-            try_analyze_encoded(data, self.args.display_channels)
-                .await
-                .err()
-                .map(|e| log::error!("{}", e));
+            if let Err(e) = try_analyze_encoded(data, self.args.display_channels).await {
+                log::error!("{}", e);
+            }
         } else {
             if forge_data["truncated"].as_bool().unwrap_or(false) {
                 log::info!("Server truncated mod information");
@@ -137,7 +85,7 @@ impl Analyzer for ForgeInfo<'_> {
             for mod_data in mod_list {
                 let name = mod_data["modId"].as_str().unwrap_or("<unknown name>");
                 let version = mod_data["modmarker"].as_str().unwrap_or("");
-                if version == "" {
+                if version.is_empty() {
                     log::info!("Mod: {}", name);
                 } else {
                     log::info!("Mod: {} ({})", name, version);
@@ -11,13 +11,46 @@ impl Analyzer for GameMode {
     }
 
     async fn analyze(&self, payload: &StatusPayload) {
-        if let Some(mode) = payload
-            .full_extra
-            .as_ref()
-            .map(|x| x["game_mode"].as_str())
-            .flatten()
-        {
-            log::info!("Game Mode: {}", mode);
+        let Some(extra) = payload.full_extra.as_ref() else {
+            return;
+        };
+        if let Some(mode) = extra["game_mode"].as_str() {
+            match extra["game_mode_id"].as_i64() {
+                Some(id) => log::info!("Game Mode: {} ({})", mode, id),
+                None => log::info!("Game Mode: {}", mode),
+            }
+        }
+        let ports = [("IPv4", &extra["port_v4"]), ("IPv6", &extra["port_v6"])]
+            .iter()
+            .filter_map(|(family, port)| port.as_u64().map(|p| format!("{} {}", family, p)))
+            .collect::<Vec<_>>();
+        if !ports.is_empty() {
+            log::info!("Advertised Ports: {}", ports.join(", "));
+        }
+        let guid = extra["server_guid"].as_u64();
+        for check in extra["followed_ports"].as_array().into_iter().flatten() {
+            let family = check["family"].as_str().unwrap_or("?");
+            let port = &check["port"];
+            match check["server_guid"].as_u64() {
+                Some(answered) if Some(answered) == guid => log::info!(
+                    "  {} port {}: same server ({}ms)",
+                    family,
+                    port,
+                    check["ping"]
+                ),
+                Some(answered) => log::warn!(
+                    "  {} port {}: different server guid {}",
+                    family,
+                    port,
+                    answered
+                ),
+                None => log::warn!(
+                    "  {} port {}: no answer ({})",
+                    family,
+                    port,
+                    check["error"].as_str().unwrap_or("unknown error")
+                ),
+            }
         }
     }
 }
@@ -1,4 +1,7 @@
-mod favicon;
+pub mod crossplay;
+pub mod diff;
+pub mod favicon;
+mod fingerprint;
 #[cfg(feature = "analyze-forge-info")]
 mod forge_info;
 mod game_mode;
@@ -6,6 +9,9 @@ mod motd;
 mod ping;
 mod player;
 mod server_guid;
+pub mod snapshot;
+mod sweep;
+mod timing;
 mod version;
 
 use crate::analyze::favicon::FaviconArgs;
@@ -13,9 +19,12 @@ use crate::analyze::favicon::FaviconArgs;
 use crate::analyze::forge_info::ForgeInfoArgs;
 use crate::analyze::motd::{MotdArgs, sanitize_motd_args};
 use crate::analyze::player::PlayerArgs;
+use crate::analyze::snapshot::{SnapshotArgs, sanitize_snapshot_args};
+use crate::analyze::timing::TimingArgs;
 use crate::mode::QueryMode;
+use crate::network::util::Timings;
 use async_trait::async_trait;
-use clap::{Args, ValueEnum, arg};
+use clap::{Args, ValueEnum};
 use serde_json::Value;
 
 #[derive(Debug)]
@@ -34,6 +43,7 @@ pub enum MotdInfo {
 pub struct StatusPayload {
     pub mode: QueryMode,
     pub ping: i64,
+    pub clock_skew: Option<i64>,
 
     // players
     pub max_players: Option<i64>,
@@ -52,26 +62,59 @@ pub struct StatusPayload {
 
     // extra info
     pub full_extra: Option<Value>,
+
+    // latency breakdown
+    pub timings: Timings,
 }
 
 #[async_trait]
 pub trait Analyzer {
     fn enabled(&self, payload: &StatusPayload) -> bool;
     async fn analyze(&self, payload: &StatusPayload);
+    /// Writes to an output file instead of only logging
+    fn writes_output(&self) -> bool {
+        false
+    }
 }
 
 pub struct AnalyzerTools<'a> {
     analyzers: Vec<Box<dyn Analyzer + 'a>>,
+    motd: Option<motd::Motd<'a>>,
 }
 
 impl AnalyzerTools<'_> {
     pub async fn analyze(&self, payload: &StatusPayload) {
         for analyzer in self.analyzers.iter() {
-            if analyzer.enabled(&payload) {
-                analyzer.analyze(&payload).await;
+            if analyzer.enabled(payload) {
+                analyzer.analyze(payload).await;
+            }
+        }
+    }
+
+    /// Run only the analyzers that log, for views that show the same status again and again
+    pub async fn describe(&self, payload: &StatusPayload) {
+        for analyzer in self.analyzers.iter() {
+            if !analyzer.writes_output() && analyzer.enabled(payload) {
+                analyzer.analyze(payload).await;
             }
         }
     }
+
+    /// Render a Java MOTD string outside a status, when the MOTD analyzer is enabled
+    pub fn render_motd(&self, motd: &str) {
+        if let Some(renderer) = self.motd.as_ref() {
+            renderer.render_string(motd, false);
+        }
+    }
+
+    /// MOTD of a status styled like the MOTD analyzer does, plain text when it is disabled
+    pub fn styled_motd(&self, payload: &StatusPayload) -> Option<String> {
+        let motd = payload.motd.as_ref()?;
+        Some(match self.motd.as_ref() {
+            Some(renderer) => renderer.styled(motd, payload.mode == QueryMode::BEDROCK),
+            None => motd::plain_motd(motd),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, ValueEnum)]
@@ -85,6 +128,10 @@ pub enum AvailableAnalyzers {
     Motd,
     Player,
     Favicon,
+    Snapshot,
+    Timings,
+    Fingerprint,
+    Sweep,
     #[cfg(feature = "analyze-forge-info")]
     ForgeInfo,
 }
@@ -101,6 +148,10 @@ pub struct AnalyzerArgs {
     player_args: PlayerArgs,
     #[command(flatten)]
     favicon_args: FaviconArgs,
+    #[command(flatten)]
+    snapshot_args: SnapshotArgs,
+    #[command(flatten)]
+    timing_args: TimingArgs,
     #[cfg(feature = "analyze-forge-info")]
     #[command(flatten)]
     forge_info_args: ForgeInfoArgs,
@@ -115,12 +166,31 @@ pub fn sanitize_analyzer_args(args: &mut crate::BaseArgs) {
         analyzers.push(AvailableAnalyzers::Player);
         analyzers.push(AvailableAnalyzers::Favicon);
     }
-    if analyzers.contains(&AvailableAnalyzers::Motd) {
+    // Sweep results only exist when the sweep mode was asked for, report them without another flag
+    if args.mode.contains(&QueryMode::SWEEP) {
+        analyzers.push(AvailableAnalyzers::Sweep);
+    }
+    // Followed ports are reported next to the advertised ones
+    if args.mode_args.follow_ports() {
+        analyzers.push(AvailableAnalyzers::GameMode);
+    }
+    // Asking for a snapshot file is enough, it does nothing without a path
+    if args.analyzer_args.snapshot_args.snapshot.is_some() {
+        analyzers.push(AvailableAnalyzers::Snapshot);
+    }
+    if analyzers.contains(&AvailableAnalyzers::Snapshot) {
+        sanitize_snapshot_args(args);
+    }
+    if args
+        .analyzer_args
+        .analyzers
+        .contains(&AvailableAnalyzers::Motd)
+    {
         sanitize_motd_args(args);
     }
 }
 
-pub fn init_analyzer_tools(args: &'_ AnalyzerArgs) -> AnalyzerTools<'_> {
+pub fn init_analyzer_tools(args: &'_ AnalyzerArgs, client_protocol: i32) -> AnalyzerTools<'_> {
     let mut analyzers: Vec<Box<dyn Analyzer>> = Vec::new();
 
     if args.analyzers.contains(&AvailableAnalyzers::Ping) {
@@ -128,7 +198,7 @@ pub fn init_analyzer_tools(args: &'_ AnalyzerArgs) -> AnalyzerTools<'_> {
     }
 
     if args.analyzers.contains(&AvailableAnalyzers::Version) {
-        analyzers.push(Box::new(version::Version {}));
+        analyzers.push(Box::new(version::Version::new(client_protocol)));
     }
 
     if args.analyzers.contains(&AvailableAnalyzers::ServerGuid) {
@@ -151,10 +221,30 @@ pub fn init_analyzer_tools(args: &'_ AnalyzerArgs) -> AnalyzerTools<'_> {
         analyzers.push(Box::new(favicon::Favicon::new(&args.favicon_args)));
     }
 
+    if args.analyzers.contains(&AvailableAnalyzers::Snapshot) {
+        analyzers.push(Box::new(snapshot::Snapshot::new(&args.snapshot_args)));
+    }
+
+    if args.analyzers.contains(&AvailableAnalyzers::Timings) {
+        analyzers.push(Box::new(timing::Timing::new(&args.timing_args)));
+    }
+
+    if args.analyzers.contains(&AvailableAnalyzers::Sweep) {
+        analyzers.push(Box::new(sweep::Sweep {}));
+    }
+
+    if args.analyzers.contains(&AvailableAnalyzers::Fingerprint) {
+        analyzers.push(Box::new(fingerprint::Fingerprint {}));
+    }
+
     #[cfg(feature = "analyze-forge-info")]
     if args.analyzers.contains(&AvailableAnalyzers::ForgeInfo) {
         analyzers.push(Box::new(forge_info::ForgeInfo::new(&args.forge_info_args)));
     }
 
-    AnalyzerTools { analyzers }
+    let motd = args
+        .analyzers
+        .contains(&AvailableAnalyzers::Motd)
+        .then(|| motd::Motd::new(&args.motd_args));
+    AnalyzerTools { analyzers, motd }
 }
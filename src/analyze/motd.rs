@@ -37,16 +37,18 @@ pub fn sanitize_motd_args(args: &mut crate::BaseArgs) {
     if args.no_color {
         motd.no_motd_styles = true;
     }
-    if !motd.no_motd_true_colors && !motd.no_motd_styles && !motd.raw_motd {
-        if let Some(term) = std::env::var("COLORTERM").ok() {
-            if term != "truecolor" && term != "24bit" {
-                log::warn!(
-                    "Terminal doesn't support true colors, MOTD will use ANSI colors ($COLORTERM = {})",
-                    term
-                );
-                motd.no_motd_true_colors = true;
-            }
-        }
+    if !motd.no_motd_true_colors
+        && !motd.no_motd_styles
+        && !motd.raw_motd
+        && let Ok(term) = std::env::var("COLORTERM")
+        && term != "truecolor"
+        && term != "24bit"
+    {
+        log::warn!(
+            "Terminal doesn't support true colors, MOTD will use ANSI colors ($COLORTERM = {})",
+            term
+        );
+        motd.no_motd_true_colors = true;
     }
 }
 
@@ -143,13 +145,34 @@ static BEDROCK_INSTRUCTIONS: LazyLock<HashMap<char, (Option<TrueColor>, Formatte
         output
     });
 
-fn no_color_motd_string(str: &str) {
-    log::info!(
-        "{}",
-        Regex::new("§.")
-            .expect("Could not compile regex")
-            .replace_all(str, "")
-    );
+static FORMAT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("§.").expect("Could not compile regex"));
+
+fn component_text(component: &Value, output: &mut String) {
+    match component {
+        Value::String(text) => output.push_str(text),
+        Value::Array(components) => components.iter().for_each(|c| component_text(c, output)),
+        Value::Object(object) => {
+            output.push_str(try_get(object, "text").as_str().unwrap_or(""));
+            if let Some(extra) = object.get("extra") {
+                component_text(extra, output);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// MOTD as displayed text without formatting codes or styles
+pub fn plain_motd(motd: &MotdInfo) -> String {
+    let text = match motd {
+        MotdInfo::String(motd) => motd.clone(),
+        MotdInfo::Component(component) => {
+            let mut text = String::new();
+            component_text(component, &mut text);
+            text
+        }
+    };
+    FORMAT_REGEX.replace_all(&text, "").to_string()
 }
 
 fn copy_style(style: &ColoredString, chars: &[char]) -> ColoredString {
@@ -160,7 +183,7 @@ fn copy_style(style: &ColoredString, chars: &[char]) -> ColoredString {
     make
 }
 
-fn color_motd_string(str: &str, be: bool, true_color: bool) {
+fn color_motd_string(str: &str, be: bool, true_color: bool) -> String {
     let mut last_style = default_style(true_color);
 
     let chars: Vec<char> = str.chars().collect();
@@ -206,8 +229,7 @@ fn color_motd_string(str: &str, be: bool, true_color: bool) {
     }
 
     slices.push(copy_style(&last_style, &chars[last_index..]));
-    slices.iter().for_each(|slice| print!("{}", slice));
-    println!();
+    slices.iter().map(ColoredString::to_string).collect()
 }
 
 fn str_to_chars(str: &str) -> Vec<char> {
@@ -228,8 +250,7 @@ fn make_text_component(
             .as_array()
             .expect("Should be array")
             .iter()
-            .map(|c| make_text_component(c, base_style, true_color))
-            .flatten()
+            .flat_map(|c| make_text_component(c, base_style, true_color))
             .collect();
     }
 
@@ -303,7 +324,9 @@ fn make_text_component(
         .get("extra")
         .map(|e| make_text_component(e, &my_style, true_color));
     let mut subs = vec![my_style];
-    extra.map(|extra| subs.extend(extra));
+    if let Some(extra) = extra {
+        subs.extend(extra);
+    }
     subs
 }
 
@@ -315,40 +338,46 @@ impl Analyzer for Motd<'_> {
 
     async fn analyze(&self, payload: &StatusPayload) {
         let motd = payload.motd.as_ref().expect("No motd found");
+        log::info!("{}", self.styled(motd, payload.mode == QueryMode::BEDROCK));
+    }
+}
+
+impl Motd<'_> {
+    pub fn new(args: &'_ MotdArgs) -> Motd<'_> {
+        Motd { args }
+    }
+
+    /// Render a `§` formatted string, Bedrock strings use a different palette
+    pub fn render_string(&self, motd_string: &str, be: bool) {
+        log::info!("{}", self.styled_string(motd_string, be));
+    }
+
+    fn styled_string(&self, motd_string: &str, be: bool) -> String {
+        if self.args.raw_motd {
+            motd_string.to_string()
+        } else if self.args.no_motd_styles {
+            FORMAT_REGEX.replace_all(motd_string, "").to_string()
+        } else {
+            color_motd_string(motd_string, be, !self.args.no_motd_true_colors)
+        }
+    }
+
+    /// MOTD with terminal styles as the options ask for, possibly spanning several lines
+    pub fn styled(&self, motd: &MotdInfo, be: bool) -> String {
         match motd {
-            MotdInfo::String(motd_string) => {
-                if self.args.raw_motd {
-                    log::info!("{}", motd_string);
-                } else if self.args.no_motd_styles {
-                    no_color_motd_string(motd_string);
-                } else {
-                    color_motd_string(
-                        motd_string,
-                        payload.mode == QueryMode::BEDROCK,
-                        !self.args.no_motd_true_colors,
-                    );
-                }
-            }
+            MotdInfo::String(motd_string) => self.styled_string(motd_string, be),
             MotdInfo::Component(s) => {
                 if self.args.raw_motd {
-                    log::info!("{}", s);
-                    return;
+                    return s.to_string();
                 }
                 let true_color = !self.args.no_motd_true_colors;
                 let texts = make_text_component(s, &default_style(true_color), true_color);
                 if self.args.no_motd_styles {
-                    texts.iter().for_each(|s| print!("{}", s.input));
+                    texts.iter().map(|s| s.input.as_str()).collect()
                 } else {
-                    texts.iter().for_each(|s| print!("{}", s));
+                    texts.iter().map(ColoredString::to_string).collect()
                 }
-                println!();
             }
         }
     }
 }
-
-impl Motd<'_> {
-    pub fn new(args: &'_ MotdArgs) -> Motd<'_> {
-        Motd { args }
-    }
-}
@@ -1,5 +1,5 @@
-use async_trait::async_trait;
 use crate::analyze::{Analyzer, StatusPayload};
+use async_trait::async_trait;
 
 pub struct Ping;
 
@@ -11,5 +11,21 @@ impl Analyzer for Ping {
 
     async fn analyze(&self, payload: &StatusPayload) {
         log::info!("Ping to server: {}ms", payload.ping);
+        if let Some(skew) = payload.clock_skew {
+            log::info!("Server clock skew: {:+}ms", skew);
+        }
+        if let Some(extra) = payload.full_extra.as_ref()
+            && let Some(attempts) = extra["ping_attempts"].as_u64()
+            && let Some(answered) = extra["ping_answered"].as_u64()
+        {
+            // Pings sent before the answered one had their full interval to come back
+            let loss = (answered - 1) as f64 / answered as f64 * 100.0;
+            log::info!(
+                "Ping attempts: {} (answered #{}), loss: {:.0}%",
+                attempts,
+                answered,
+                loss
+            );
+        }
     }
-}
\ No newline at end of file
+}
@@ -14,8 +14,7 @@ impl Analyzer for ServerGuid {
         if let Some(guid) = payload
             .full_extra
             .as_ref()
-            .map(|x| x["server_guid"].as_u64())
-            .flatten()
+            .and_then(|x| x["server_guid"].as_u64())
         {
             log::info!("Server guid: {}", guid);
         }
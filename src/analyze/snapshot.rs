@@ -0,0 +1,162 @@
+use crate::analyze::favicon::favicon_hash;
+use crate::analyze::{Analyzer, MotdInfo, PlayerInfo, StatusPayload};
+use crate::mode::QueryMode;
+use crate::network::util::{Timings, now_timestamp};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, ValueEnum};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+/// Bumped when a field changes meaning, older snapshots are refused instead of misread
+const SNAPSHOT_VERSION: i64 = 1;
+
+#[derive(Args, Debug)]
+pub struct SnapshotArgs {
+    /// Save the status to a snapshot file, to be compared later with `diff`
+    #[arg(long)]
+    pub snapshot: Option<PathBuf>,
+}
+
+/// A snapshot file holds one status, several answers would silently overwrite each other
+pub fn sanitize_snapshot_args(args: &crate::BaseArgs) {
+    if args.analyzer_args.snapshot_args.snapshot.is_none() {
+        return;
+    }
+    let conflict = match &args.command {
+        Some(crate::Command::Discover(_)) => Some("discover"),
+        Some(crate::Command::ServersDat(_)) => Some("servers-dat"),
+        Some(crate::Command::Dashboard(_)) => Some("dashboard"),
+        _ if args.run_all_modes && args.mode.len() > 1 => {
+            Some("--run-all-modes with several modes")
+        }
+        _ => None,
+    };
+    if let Some(conflict) = conflict {
+        crate::BaseArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--snapshot saves a single status and cannot be used with {}",
+                    conflict
+                ),
+            )
+            .exit();
+    }
+}
+
+pub struct Snapshot<'a> {
+    args: &'a SnapshotArgs,
+}
+
+fn to_snapshot(payload: &StatusPayload) -> Value {
+    let motd = match payload.motd.as_ref() {
+        Some(MotdInfo::String(motd)) => json!(motd),
+        Some(MotdInfo::Component(component)) => component.clone(),
+        None => Value::Null,
+    };
+    let players = payload.players.as_ref().map(|players| {
+        players
+            .iter()
+            .map(|p| json!({"name": p.id, "id": p.uuid}))
+            .collect::<Vec<_>>()
+    });
+    json!({
+        "snapshot_version": SNAPSHOT_VERSION,
+        "taken_at_ms": now_timestamp(),
+        "mode": format!("{:?}", payload.mode).to_lowercase(),
+        "ping": payload.ping,
+        "protocol": payload.protocol,
+        "version_name": payload.version_name,
+        "player_count": payload.player_count,
+        "max_players": payload.max_players,
+        "players": players,
+        "motd": motd,
+        "favicon": payload.favicon,
+        "favicon_hash": payload.favicon.as_deref().map(favicon_hash),
+        "full_extra": payload.full_extra,
+    })
+}
+
+fn from_snapshot(snapshot: &Value) -> Result<StatusPayload> {
+    if snapshot["snapshot_version"].as_i64() != Some(SNAPSHOT_VERSION) {
+        return Err(anyhow!(
+            "Unsupported snapshot version {}",
+            snapshot["snapshot_version"]
+        ));
+    }
+    let mode = snapshot["mode"]
+        .as_str()
+        .ok_or(anyhow!("Snapshot has no mode"))?;
+    let mode = QueryMode::from_str(mode, true).map_err(|e| anyhow!("Snapshot mode {}", e))?;
+    // Java answers a string or a component object, other editions only strings
+    let motd = match &snapshot["motd"] {
+        Value::String(motd) => Some(MotdInfo::String(motd.clone())),
+        Value::Null => None,
+        component => Some(MotdInfo::Component(component.clone())),
+    };
+    let players = snapshot["players"].as_array().map(|players| {
+        players
+            .iter()
+            .map(|p| PlayerInfo {
+                id: p["name"].as_str().unwrap_or_default().to_string(),
+                uuid: p["id"].as_str().unwrap_or_default().to_string(),
+            })
+            .collect()
+    });
+    Ok(StatusPayload {
+        mode,
+        ping: snapshot["ping"].as_i64().unwrap_or_default(),
+        clock_skew: None,
+        max_players: snapshot["max_players"].as_i64(),
+        player_count: snapshot["player_count"].as_i64(),
+        players,
+        motd,
+        protocol: snapshot["protocol"].as_i64(),
+        version_name: snapshot["version_name"].as_str().map(str::to_string),
+        favicon: snapshot["favicon"].as_str().map(str::to_string),
+        full_extra: Some(snapshot["full_extra"].clone()).filter(|x| !x.is_null()),
+        timings: Timings::default(),
+    })
+}
+
+pub async fn read_snapshot(path: &Path) -> Result<StatusPayload> {
+    let text = tokio::fs::read_to_string(path).await?;
+    from_snapshot(&serde_json::from_str(&text)?)
+}
+
+async fn write_snapshot(path: &Path, payload: &StatusPayload) -> Result<()> {
+    let text = serde_json::to_string_pretty(&to_snapshot(payload))?;
+    tokio::fs::write(path, text).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl Analyzer for Snapshot<'_> {
+    fn enabled(&self, _: &StatusPayload) -> bool {
+        self.args.snapshot.is_some()
+    }
+
+    async fn analyze(&self, payload: &StatusPayload) {
+        let path = self
+            .args
+            .snapshot
+            .as_ref()
+            .expect("No snapshot path provided");
+        match write_snapshot(path, payload).await {
+            Ok(()) => log::info!("Snapshot saved to {}", path.display()),
+            Err(e) => log::error!("Snapshot output error: {}", e),
+        }
+    }
+
+    fn writes_output(&self) -> bool {
+        true
+    }
+}
+
+impl Snapshot<'_> {
+    pub fn new(args: &'_ SnapshotArgs) -> Snapshot<'_> {
+        Snapshot { args }
+    }
+}
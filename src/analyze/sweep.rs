@@ -0,0 +1,129 @@
+use crate::analyze::{Analyzer, StatusPayload};
+use crate::network::schema::protocol::describe_java_protocol;
+use async_trait::async_trait;
+use serde_json::Value;
+
+fn protocol_name(protocol: i64) -> String {
+    i32::try_from(protocol)
+        .ok()
+        .and_then(describe_java_protocol)
+        .unwrap_or_else(|| "<UNKNOWN>".to_string())
+}
+
+fn distinct<'a>(values: impl Iterator<Item = &'a Value>) -> Vec<&'a Value> {
+    let mut seen = vec![];
+    for value in values {
+        if !seen.contains(&value) {
+            seen.push(value);
+        }
+    }
+    seen
+}
+
+pub struct Sweep;
+
+#[async_trait]
+impl Analyzer for Sweep {
+    fn enabled(&self, payload: &StatusPayload) -> bool {
+        payload
+            .full_extra
+            .as_ref()
+            .is_some_and(|x| x["sweep"].is_array())
+    }
+
+    async fn analyze(&self, payload: &StatusPayload) {
+        let Some(entries) = payload
+            .full_extra
+            .as_ref()
+            .and_then(|x| x["sweep"].as_array())
+        else {
+            return;
+        };
+        let answered = entries
+            .iter()
+            .filter(|e| e["protocol"].is_i64())
+            .collect::<Vec<_>>();
+
+        log::info!("Protocol sweep:");
+        for entry in entries {
+            let sent = entry["sent"].as_i64().unwrap_or_default();
+            match entry["protocol"].as_i64() {
+                Some(protocol) => log::info!(
+                    "  {:>5} {:17} -> {:>5} {}",
+                    sent,
+                    protocol_name(sent),
+                    protocol,
+                    entry["version_name"].as_str().unwrap_or("<UNKNOWN>")
+                ),
+                None => log::info!(
+                    "  {:>5} {:17} -> {}",
+                    sent,
+                    protocol_name(sent),
+                    entry["error"].as_str().unwrap_or("<NO RESPONSE>")
+                ),
+            }
+        }
+        if answered.is_empty() {
+            log::warn!("No protocol in the sweep was answered");
+            return;
+        }
+
+        let echoed = answered
+            .iter()
+            .filter(|e| e["protocol"] == e["sent"])
+            .filter_map(|e| e["sent"].as_i64())
+            .collect::<Vec<_>>();
+        let fixed = distinct(
+            answered
+                .iter()
+                .filter(|e| e["protocol"] != e["sent"])
+                .map(|e| &e["protocol"]),
+        );
+        log::info!(
+            "Server echoes the client protocol for {} of {} answers",
+            echoed.len(),
+            answered.len()
+        );
+        if let (Some(first), Some(last)) = (echoed.iter().min(), echoed.iter().max())
+            && echoed.len() > 1
+        {
+            log::info!(
+                "Supported range: {} ({}) to {} ({}), the server translates versions",
+                protocol_name(*first),
+                first,
+                protocol_name(*last),
+                last
+            );
+        }
+        match fixed.as_slice() {
+            [] => {}
+            [native] => {
+                let native = native.as_i64().unwrap_or_default();
+                log::info!("Native protocol: {} ({})", protocol_name(native), native);
+            }
+            _ => log::info!(
+                "Server answered with {} different fixed protocols",
+                fixed.len()
+            ),
+        }
+
+        let names = distinct(answered.iter().map(|e| &e["version_name"]));
+        if names.len() > 1 {
+            let names = names
+                .iter()
+                .map(|n| n.as_str().unwrap_or("<UNKNOWN>"))
+                .collect::<Vec<_>>();
+            log::info!(
+                "Version name changes with client protocol: {}",
+                names.join(", ")
+            );
+        }
+        let motds = distinct(answered.iter().map(|e| &e["motd"]));
+        if motds.len() > 1 {
+            log::info!(
+                "MOTD changes with client protocol: {} variants",
+                motds.len()
+            );
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::analyze::{Analyzer, StatusPayload};
+use async_trait::async_trait;
+use clap::Args;
+use std::time::Duration;
+
+#[derive(Args, Debug)]
+pub struct TimingArgs {
+    /// Output latency breakdown as JSON
+    #[arg(long)]
+    timings_json: bool,
+}
+
+pub struct Timing<'a> {
+    args: &'a TimingArgs,
+}
+
+fn log_phase(name: &str, time: Option<Duration>) {
+    if let Some(time) = time {
+        log::info!("  {:20} {:.1}ms", name, time.as_secs_f64() * 1000.0);
+    }
+}
+
+#[async_trait]
+impl Analyzer for Timing<'_> {
+    fn enabled(&self, _payload: &StatusPayload) -> bool {
+        true
+    }
+
+    async fn analyze(&self, payload: &StatusPayload) {
+        let timings = &payload.timings;
+        if self.args.timings_json {
+            log::info!("{}", timings.to_json());
+            return;
+        }
+        log::info!("Latency breakdown:");
+        log_phase("DNS lookup", timings.dns);
+        log_phase("Connect", timings.connect);
+        log_phase("Status response", timings.status);
+        log_phase("Round trip", timings.round_trip);
+        if let Some(size) = timings.status_size {
+            log::info!("  {:20} {} bytes", "Status size", size);
+        }
+    }
+}
+
+impl Timing<'_> {
+    pub fn new(args: &'_ TimingArgs) -> Timing<'_> {
+        Timing { args }
+    }
+}
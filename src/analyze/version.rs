@@ -1,7 +1,42 @@
 use crate::analyze::{Analyzer, StatusPayload};
+use crate::mode::QueryMode::{BEDROCK, JAVA};
+use crate::network::schema::protocol::{
+    BEDROCK_PROTOCOLS, JAVA_PROTOCOLS, ProtocolEntry, describe_java_protocol, find_protocol,
+};
 use async_trait::async_trait;
+use regex_lite::Regex;
+use std::sync::LazyLock;
 
-pub struct Version;
+static VERSION_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d+\.\d+(?:\.\d+)?").expect("Compile regex failed!"));
+
+pub struct Version {
+    client_protocol: i32,
+}
+
+impl Version {
+    pub fn new(client_protocol: i32) -> Self {
+        Version { client_protocol }
+    }
+}
+
+fn check_name(entry: &ProtocolEntry, version_name: &str) {
+    let versions = VERSION_REGEX
+        .find_iter(version_name)
+        .map(|m| m.as_str())
+        .collect::<Vec<_>>();
+    // Proxies advertise ranges like `1.8.x-1.21.x`, only a single version can be compared
+    if let [version] = versions.as_slice()
+        && !entry.contains(version)
+    {
+        log::warn!(
+            "Reported version {} does not match protocol {} ({})",
+            version,
+            entry.protocol,
+            entry.name()
+        );
+    }
+}
 
 #[async_trait]
 impl Analyzer for Version {
@@ -14,18 +49,72 @@ impl Analyzer for Version {
             && let Some(legacy) = extra["legacy_version"].as_i64()
         {
             log::info!("Legacy Response Version: {}", legacy);
+            if let Some(dialect) = extra["legacy_dialect"].as_str() {
+                log::info!("Legacy Ping Dialect: {}", dialect);
+            }
+        }
+
+        if let Some(extra) = payload.full_extra.as_ref()
+            && let Some(families) = extra["protocol_families"].as_array()
+        {
+            let families = families
+                .iter()
+                .filter_map(|f| f.as_str())
+                .collect::<Vec<_>>();
+            log::info!("Protocol Families: {}", families.join(", "));
         }
 
+        let protocol = payload.protocol.and_then(|p| i32::try_from(p).ok());
+        let entry = match (payload.mode, protocol) {
+            (JAVA, Some(protocol)) => find_protocol(JAVA_PROTOCOLS, protocol),
+            (BEDROCK, Some(protocol)) => find_protocol(BEDROCK_PROTOCOLS, protocol),
+            _ => None,
+        };
+
         let protocol_num = if let Some(protocol) = payload.protocol {
             protocol.to_string()
         } else {
             "<UNKNOWN>".to_string()
         };
         let version_name = if let Some(name) = payload.version_name.as_ref() {
-            name
+            name.to_string()
+        } else if let Some(entry) = entry {
+            entry.name()
+        } else if payload.mode == JAVA
+            && let Some(description) = protocol.and_then(describe_java_protocol)
+        {
+            description
         } else {
-            "<UNKNOWN>"
+            "<UNKNOWN>".to_string()
         };
         log::info!("Version: {} ({})", version_name, protocol_num);
+
+        if let Some(entry) = entry {
+            log::info!("Protocol {} = {}", entry.protocol, entry.name());
+            if let Some(name) = payload.version_name.as_ref() {
+                check_name(entry, name);
+            }
+        } else if payload.mode == JAVA
+            && let Some(protocol) = protocol
+            && let Some(description) = describe_java_protocol(protocol)
+        {
+            log::info!("Protocol {:#x} = {}", protocol, description);
+        }
+
+        if payload.mode == JAVA
+            && let Some(protocol) = protocol
+        {
+            let client = describe_java_protocol(self.client_protocol)
+                .unwrap_or_else(|| format!("protocol {}", self.client_protocol));
+            if protocol == self.client_protocol {
+                log::info!("Compatible with the selected {} client", client);
+            } else {
+                log::warn!(
+                    "The selected {} client ({}) would show this server as incompatible",
+                    client,
+                    self.client_protocol
+                );
+            }
+        }
     }
 }
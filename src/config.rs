@@ -0,0 +1,339 @@
+use crate::BaseArgs;
+use crate::network::resolve::sanitize_addr;
+use anyhow::{Result, anyhow};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// Table of server profiles, every other top-level key is the default of the option with that long name
+const SERVERS_KEY: &str = "servers";
+
+fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("mcping").join("config.toml"))
+}
+
+/// An explicit `--config` must exist, the default location is optional
+fn read_config(explicit: Option<&PathBuf>) -> Result<Option<Table>> {
+    let path = match explicit {
+        Some(path) => path.clone(),
+        None => match default_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        },
+    };
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Cannot read config {}: {}", path.display(), e))?;
+    let table = text
+        .parse::<Table>()
+        .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))?;
+    Ok(Some(table))
+}
+
+fn scalar(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Datetime(_) => {
+            Ok(value.to_string())
+        }
+        _ => Err(anyhow!("expected a string, number or boolean")),
+    }
+}
+
+/// Turn option defaults into command line arguments, leaving out options given on the command line
+fn option_args(options: &Table, matches: &ArgMatches) -> Result<Vec<OsString>> {
+    let command = BaseArgs::command();
+    let mut argv = vec![];
+    for (key, value) in options {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key))
+            .ok_or(anyhow!("Unknown option '{}' in config", key))?;
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", key);
+        if !arg.get_action().takes_values() {
+            match value {
+                Value::Boolean(true) => argv.push(flag.into()),
+                Value::Boolean(false) => {}
+                _ => return Err(anyhow!("Option '{}' in config expects a boolean", key)),
+            }
+            continue;
+        }
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = scalar(value).map_err(|e| anyhow!("Option '{}' in config: {}", key, e))?;
+            argv.push(format!("{}={}", flag, value).into());
+        }
+    }
+    Ok(argv)
+}
+
+/// Lay a server profile over the defaults, returning the address it points to
+fn apply_profile(name: &str, profile: &Value, options: &mut Table) -> Result<String> {
+    let Value::Table(profile) = profile else {
+        return Err(anyhow!("Server profile '{}' must be a table", name));
+    };
+    let mut profile = profile.clone();
+    let address = match profile.remove("address") {
+        Some(address) => scalar(&address)?,
+        None => name.to_string(),
+    };
+    let address = match profile.remove("port") {
+        Some(port) => {
+            let port = scalar(&port)?
+                .parse::<u16>()
+                .map_err(|e| anyhow!("Port of server profile '{}' is invalid: {}", name, e))?;
+            // Appending to an address with its own port would give `host:port:port`
+            let (host, _) = sanitize_addr(&address, port)?;
+            if host != address {
+                return Err(anyhow!(
+                    "Server profile '{}' sets a port in both 'address' and 'port'",
+                    name
+                ));
+            }
+            format!("{}:{}", address, port)
+        }
+        None => address,
+    };
+    options.extend(profile);
+    Ok(address)
+}
+
+/// Every server profile in config order, with its address and the arguments of the command
+/// line once the profile is applied, for commands watching several servers
+pub fn server_profiles(explicit: Option<&PathBuf>) -> Result<Vec<(String, String, BaseArgs)>> {
+    server_profiles_from(std::env::args_os().collect(), explicit)
+}
+
+fn server_profiles_from(
+    cli: Vec<OsString>,
+    explicit: Option<&PathBuf>,
+) -> Result<Vec<(String, String, BaseArgs)>> {
+    let Some(mut config) = read_config(explicit)? else {
+        return Ok(vec![]);
+    };
+    let servers = match config.remove(SERVERS_KEY) {
+        Some(Value::Table(servers)) => servers,
+        Some(_) => return Err(anyhow!("'{}' in config must be a table", SERVERS_KEY)),
+        None => return Ok(vec![]),
+    };
+    servers
+        .keys()
+        .map(|name| {
+            let (args, address) = layered_args(cli.clone(), Some(name))?;
+            Ok((
+                name.clone(),
+                address.expect("Profile should be applied"),
+                args,
+            ))
+        })
+        .collect()
+}
+
+/// Parse the command line on top of the config file
+///
+/// Precedence from highest to lowest: command line flags, the server profile named by the
+/// address, top-level config defaults, then environment variables (`ALL_PROXY`, `NO_PROXY`...)
+/// which only fill in what is still unset.
+pub fn parse_args() -> Result<BaseArgs> {
+    parse_args_from(std::env::args_os().collect())
+}
+
+fn parse_args_from(cli: Vec<OsString>) -> Result<BaseArgs> {
+    let (mut args, profile_address) = layered_args(cli, None)?;
+    if profile_address.is_some() {
+        args.address = profile_address;
+    }
+    Ok(args)
+}
+
+/// Arguments with the config defaults and a server profile laid under the command line,
+/// along with the profile address. Without `profile`, the address names the profile.
+fn layered_args(cli: Vec<OsString>, profile: Option<&str>) -> Result<(BaseArgs, Option<String>)> {
+    let matches = BaseArgs::command().get_matches_from(&cli);
+    let args = BaseArgs::from_arg_matches(&matches)?;
+    let Some(mut config) = read_config(args.config.as_ref())? else {
+        return Ok((args, None));
+    };
+
+    let servers = match config.remove(SERVERS_KEY) {
+        Some(Value::Table(servers)) => servers,
+        Some(_) => return Err(anyhow!("'{}' in config must be a table", SERVERS_KEY)),
+        None => Table::new(),
+    };
+    let profile = profile.or(args.address.as_deref().filter(|_| args.command.is_none()));
+    let profile_address = match profile {
+        Some(name) if servers.contains_key(name) => {
+            Some(apply_profile(name, &servers[name], &mut config)?)
+        }
+        _ => None,
+    };
+
+    let mut argv = cli.first().cloned().into_iter().collect::<Vec<_>>();
+    argv.extend(option_args(&config, &matches)?);
+    argv.extend(cli.into_iter().skip(1));
+    Ok((BaseArgs::parse_from(argv), profile_address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::QueryMode;
+    use crate::network::connection::sanitize_proxy_settings;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Run `f` on `cli` with a config file holding `toml`
+    fn with_config<T>(toml: &str, cli: &[&str], f: impl FnOnce(Vec<OsString>, &PathBuf) -> T) -> T {
+        // Tests run in parallel, every one needs its own file
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "mcping-config-test-{}-{}.toml",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, toml).expect("Should write config");
+        let mut argv = vec![
+            "mcping".into(),
+            "--config".into(),
+            path.clone().into_os_string(),
+        ];
+        argv.extend(cli.iter().map(OsString::from));
+        let result = f(argv, &path);
+        let _ = std::fs::remove_file(path);
+        result
+    }
+
+    fn parse(toml: &str, cli: &[&str]) -> Result<BaseArgs> {
+        with_config(toml, cli, |argv, _| parse_args_from(argv))
+    }
+
+    const CONFIG: &str = r#"
+read-timeout = "3s"
+mode = ["java"]
+proxy = "socks5h://config.proxy:1080"
+
+[servers.survival]
+address = "mc.example.com"
+port = 25566
+read-timeout = "7s"
+
+[servers."play.example.com"]
+mode = "bedrock"
+"#;
+
+    #[test]
+    fn config_defaults_fill_unset_options() {
+        let args = parse(CONFIG, &["other.example.com"]).unwrap();
+        assert_eq!(args.address.as_deref(), Some("other.example.com"));
+        assert_eq!(args.timeout_settings.read_timeout, Duration::from_secs(3));
+        assert_eq!(args.mode, [QueryMode::JAVA]);
+    }
+
+    #[test]
+    fn profile_overrides_config_defaults() {
+        let args = parse(CONFIG, &["survival"]).unwrap();
+        assert_eq!(args.address.as_deref(), Some("mc.example.com:25566"));
+        assert_eq!(args.timeout_settings.read_timeout, Duration::from_secs(7));
+        assert_eq!(args.mode, [QueryMode::JAVA]);
+
+        // A profile without `address` points to its own name
+        let args = parse(CONFIG, &["play.example.com"]).unwrap();
+        assert_eq!(args.address.as_deref(), Some("play.example.com"));
+        assert_eq!(args.mode, [QueryMode::BEDROCK]);
+    }
+
+    #[test]
+    fn command_line_overrides_profile() {
+        let args = parse(
+            CONFIG,
+            &["survival", "--read-timeout", "1s", "-m", "bedrock"],
+        )
+        .unwrap();
+        assert_eq!(args.address.as_deref(), Some("mc.example.com:25566"));
+        assert_eq!(args.timeout_settings.read_timeout, Duration::from_secs(1));
+        assert_eq!(args.mode, [QueryMode::BEDROCK]);
+    }
+
+    #[test]
+    fn environment_only_fills_unset_options() {
+        let mut args = parse(CONFIG, &["survival"]).unwrap();
+        sanitize_proxy_settings(&mut args.proxy_settings);
+        assert_eq!(
+            args.proxy_settings.proxy.as_deref(),
+            Some("socks5h://config.proxy:1080")
+        );
+    }
+
+    #[test]
+    fn profiles_of_commands() {
+        let profiles = with_config(
+            CONFIG,
+            &["--mode=java,bedrock", "dashboard"],
+            |argv, path| server_profiles_from(argv, Some(path)),
+        )
+        .unwrap();
+        let names = profiles
+            .iter()
+            .map(|(name, address, _)| (name.as_str(), address.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("survival", "mc.example.com:25566"),
+                ("play.example.com", "play.example.com")
+            ]
+        );
+        // Each profile is laid under the command line, which still wins
+        let (_, _, survival) = &profiles[0];
+        assert_eq!(
+            survival.timeout_settings.read_timeout,
+            Duration::from_secs(7)
+        );
+        assert_eq!(survival.mode, [QueryMode::JAVA, QueryMode::BEDROCK]);
+        assert!(survival.command.is_some());
+        let (_, _, play) = &profiles[1];
+        assert_eq!(play.timeout_settings.read_timeout, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn profile_ports() {
+        let profile = |toml: &str| {
+            let value = toml.parse::<Table>().unwrap();
+            apply_profile("server", &Value::Table(value), &mut Table::new())
+        };
+        assert_eq!(
+            profile("address = 'mc.example.com'").unwrap(),
+            "mc.example.com"
+        );
+        assert_eq!(
+            profile("address = 'mc.example.com:25566'").unwrap(),
+            "mc.example.com:25566"
+        );
+        assert_eq!(
+            profile("address = 'mc.example.com'\nport = '25566'").unwrap(),
+            "mc.example.com:25566"
+        );
+        assert_eq!(profile("port = 25566").unwrap(), "server:25566");
+        let conflict = profile("address = 'mc.example.com:25565'\nport = 25566").unwrap_err();
+        assert!(conflict.to_string().contains("both"), "{}", conflict);
+        assert!(profile("address = 'mc.example.com'\nport = 70000").is_err());
+    }
+
+    #[test]
+    fn invalid_configs() {
+        assert!(parse("servers = 1", &["survival"]).is_err());
+        assert!(parse("unknown-option = 1", &["survival"]).is_err());
+        assert!(parse("[servers]\nsurvival = 'mc.example.com'", &["survival"]).is_err());
+    }
+}
@@ -0,0 +1,536 @@
+//! Live table of servers refreshed on an interval, like `top` for server lists.
+
+use crate::BaseArgs;
+use crate::analyze::favicon::favicon_preview;
+use crate::analyze::{AnalyzerTools, StatusPayload};
+use crate::config::server_profiles;
+use crate::exit::Outcome;
+use crate::logger;
+use crate::mode::{QueryEngine, QueryMode, init_query_engine};
+use crate::network::error::{FailureKind, failure_kind, local_failure_kind};
+use crate::network::util::parse_duration;
+use crate::run::{SERVER_LIST_CONCURRENCY, failure_table, query_modes};
+use anyhow::{Result, anyhow};
+use clap::Args;
+use colored::{Color, Colorize};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use crossterm::{execute, queue};
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use std::collections::VecDeque;
+use std::io::{IsTerminal, Write, stdout};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior, interval};
+
+/// Pings kept for the sparkline, one character each
+const HISTORY: usize = 30;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Favicons are 64 pixels wide, half of them still show what the icon is
+const FAVICON_COLUMNS: usize = 32;
+const RESET: &str = "\x1b[0m";
+/// Faster refreshes would hammer the servers and could not be read anyway
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Args, Debug)]
+pub struct DashboardArgs {
+    /// Addresses or server profile names to watch [default: every server profile of the config].
+    /// Profiles bring their own modes and mode options, timeouts and proxies stay global
+    pub servers: Vec<String>,
+    /// Time between two queries of the same server
+    #[arg(long, value_parser = parse_interval, default_value = "5s")]
+    pub interval: Duration,
+}
+
+fn parse_interval(value: &str) -> Result<Duration> {
+    let interval = parse_duration(value)?;
+    if interval < MIN_INTERVAL {
+        return Err(anyhow!(
+            "Interval must be at least {}s",
+            MIN_INTERVAL.as_secs()
+        ));
+    }
+    Ok(interval)
+}
+
+struct Server {
+    name: String,
+    address: String,
+    /// Settings of the server profile it comes from, `None` queries with the command line ones
+    profile: Option<usize>,
+    pings: VecDeque<Option<i64>>,
+    last: Option<(QueryMode, Result<StatusPayload>)>,
+    updated: Option<Instant>,
+    querying: bool,
+}
+
+enum View {
+    Table,
+    Detail { scroll: usize },
+}
+
+enum Action {
+    Continue,
+    Refresh,
+    Quit,
+}
+
+/// Raw mode on an alternate screen, restored when dropped so errors leave a usable terminal
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        if !stdout().is_terminal() {
+            return Err(anyhow!("The dashboard needs a terminal"));
+        }
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Cut a styled line to `width` visible characters, escape sequences are kept and closed
+fn fit(line: &str, width: usize) -> String {
+    let mut output = String::new();
+    let mut visible = 0;
+    let mut styled = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            output.push(c);
+            for c in chars.by_ref() {
+                output.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            styled = true;
+        } else if visible < width {
+            output.push(c);
+            visible += 1;
+        }
+    }
+    if styled {
+        output.push_str(RESET);
+    }
+    output
+}
+
+/// Split styled text into lines, carrying styles still open at a line break to the next line
+fn styled_lines(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut open = String::new();
+    for line in text.lines() {
+        lines.push(format!("{}{}", open, line));
+        let mut rest = line;
+        while let Some(start) = rest.find('\x1b') {
+            let end = rest[start..]
+                .find(|c: char| c.is_ascii_alphabetic())
+                .map_or(rest.len(), |end| start + end + 1);
+            match &rest[start..end] {
+                RESET => open.clear(),
+                sequence => open.push_str(sequence),
+            }
+            rest = &rest[end..];
+        }
+    }
+    lines
+}
+
+fn sparkline(pings: &VecDeque<Option<i64>>) -> String {
+    let max = pings.iter().flatten().copied().max().unwrap_or(0).max(1);
+    pings
+        .iter()
+        .map(|ping| match ping {
+            Some(ping) => SPARKS[(*ping * (SPARKS.len() as i64 - 1) / max) as usize],
+            None => '·',
+        })
+        .collect()
+}
+
+fn cell(text: &str, width: usize) -> String {
+    format!("{:width$}", text.chars().take(width).collect::<String>())
+}
+
+async fn probe(
+    engine: &QueryEngine<'_>,
+    modes: &[QueryMode],
+    index: usize,
+    address: String,
+) -> (usize, (QueryMode, Result<StatusPayload>)) {
+    // Query logs would scribble over the screen, failures are shown in the table instead
+    let (result, _) = logger::buffered(query_modes(engine, modes, &address)).await;
+    (index, result)
+}
+
+struct Dashboard<'a> {
+    servers: Vec<Server>,
+    selected: usize,
+    view: View,
+    detail: Vec<String>,
+    interval: Duration,
+    analyzers: &'a AnalyzerTools<'a>,
+    no_color: bool,
+}
+
+impl Dashboard<'_> {
+    fn paint(&self, text: String, color: Color) -> String {
+        if self.no_color {
+            text
+        } else {
+            text.color(color).to_string()
+        }
+    }
+
+    fn row(&self, index: usize, server: &Server, width: usize) -> String {
+        let marker = if index == self.selected { "> " } else { "  " };
+        let (status, ping, players, version, motd) = match &server.last {
+            None => (
+                self.paint(cell("probing", 8), Color::Yellow),
+                "-".to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ),
+            Some((_, Ok(payload))) => (
+                self.paint(cell("online", 8), Color::Green),
+                format!("{}ms", payload.ping),
+                match (payload.player_count, payload.max_players) {
+                    (Some(online), Some(max)) => format!("{}/{}", online, max),
+                    _ => String::new(),
+                },
+                payload.version_name.clone().unwrap_or_default(),
+                self.analyzers
+                    .styled_motd(payload)
+                    .and_then(|motd| styled_lines(&motd).into_iter().next())
+                    .unwrap_or_default(),
+            ),
+            Some((_, Err(e))) => (
+                self.paint(cell("offline", 8), Color::Red),
+                "-".to_string(),
+                String::new(),
+                failure_kind(e).to_string(),
+                self.paint(e.to_string(), Color::Red),
+            ),
+        };
+        let line = format!(
+            "{}{} {} {:>7} {} {:>9} {} {}",
+            marker,
+            cell(&server.name, 20),
+            status,
+            ping,
+            cell(&sparkline(&server.pings), HISTORY),
+            players,
+            cell(&version, 20),
+            motd,
+        );
+        fit(&line, width)
+    }
+
+    fn table(&self, width: usize) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} servers, every {:?}  [up/down] select  [enter] details  [r] refresh  [q] quit",
+                self.servers.len(),
+                self.interval
+            ),
+            String::new(),
+            format!(
+                "  {} {:8} {:>7} {} {:>9} {} MOTD",
+                cell("Server", 20),
+                "Status",
+                "Ping",
+                cell("History", HISTORY),
+                "Players",
+                cell("Version", 20),
+            ),
+        ];
+        for (index, server) in self.servers.iter().enumerate() {
+            lines.push(self.row(index, server, width));
+        }
+        lines
+    }
+
+    /// What the analyzers report on the selected server, output files are left to the plain CLI
+    async fn load_detail(&mut self) {
+        let server = &self.servers[self.selected];
+        let mut lines = vec![];
+        match &server.last {
+            None => lines.push("Waiting for the first answer".to_string()),
+            Some((mode, Ok(payload))) => {
+                lines.push(format!("Answered to mode {:?}", mode));
+                let (_, output) = logger::buffered(self.analyzers.describe(payload)).await;
+                lines.extend(output.iter().flat_map(|line| styled_lines(line)));
+                if !self.no_color
+                    && let Some(favicon) = payload.favicon.as_ref()
+                {
+                    match favicon_preview(favicon, FAVICON_COLUMNS) {
+                        Ok(preview) => {
+                            lines.push(String::new());
+                            lines.extend(preview);
+                        }
+                        Err(e) => lines.push(format!("Favicon cannot be previewed: {}", e)),
+                    }
+                }
+            }
+            Some((mode, Err(e))) => {
+                let (_, output) = logger::buffered(async {
+                    log::error!("Failed for mode {:?}: {}", mode, e);
+                    failure_table(e);
+                })
+                .await;
+                lines.extend(output);
+            }
+        }
+        self.detail = lines;
+    }
+
+    fn detail(&self, scroll: usize, height: usize) -> Vec<String> {
+        let server = &self.servers[self.selected];
+        let updated = server.updated.map_or("never".to_string(), |t| {
+            format!("{}s ago", t.elapsed().as_secs())
+        });
+        let mut lines = vec![
+            format!(
+                "{} ({}), updated {}  [up/down] scroll  [esc] back  [q] quit",
+                server.name, server.address, updated
+            ),
+            String::new(),
+        ];
+        lines.extend(
+            self.detail
+                .iter()
+                .skip(scroll)
+                .take(height.saturating_sub(2))
+                .cloned(),
+        );
+        lines
+    }
+
+    fn draw(&self) -> Result<()> {
+        let (width, height) = crossterm::terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let lines = match self.view {
+            View::Table => self.table(width),
+            View::Detail { scroll } => self.detail(scroll, height),
+        };
+        let mut out = stdout();
+        for (row, line) in lines.iter().take(height).enumerate() {
+            queue!(
+                out,
+                MoveTo(0, row as u16),
+                Print(fit(line, width)),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        queue!(out, Clear(ClearType::FromCursorDown))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    async fn update(&mut self, index: usize, result: (QueryMode, Result<StatusPayload>)) {
+        let server = &mut self.servers[index];
+        server
+            .pings
+            .push_back(result.1.as_ref().ok().map(|p| p.ping));
+        if server.pings.len() > HISTORY {
+            server.pings.pop_front();
+        }
+        server.last = Some(result);
+        server.updated = Some(Instant::now());
+        server.querying = false;
+        if matches!(self.view, View::Detail { .. }) && index == self.selected {
+            self.load_detail().await;
+        }
+    }
+
+    async fn key(&mut self, key: KeyEvent) -> Action {
+        if key.kind != KeyEventKind::Press {
+            return Action::Continue;
+        }
+        if key.code == KeyCode::Char('q')
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        {
+            return Action::Quit;
+        }
+        let last = self.servers.len() - 1;
+        match (&mut self.view, key.code) {
+            (View::Table, KeyCode::Up | KeyCode::Char('k')) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (View::Table, KeyCode::Down | KeyCode::Char('j')) => {
+                self.selected = (self.selected + 1).min(last)
+            }
+            (View::Table, KeyCode::Enter) => {
+                self.load_detail().await;
+                self.view = View::Detail { scroll: 0 };
+            }
+            (View::Table, KeyCode::Char('r')) => return Action::Refresh,
+            (View::Table, KeyCode::Esc) => return Action::Quit,
+            (View::Detail { scroll }, KeyCode::Up | KeyCode::Char('k')) => {
+                *scroll = scroll.saturating_sub(1)
+            }
+            (View::Detail { scroll }, KeyCode::Down | KeyCode::Char('j')) => {
+                *scroll = (*scroll + 1).min(self.detail.len().saturating_sub(1))
+            }
+            (View::Detail { .. }, KeyCode::Esc | KeyCode::Backspace | KeyCode::Left) => {
+                self.view = View::Table
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+}
+
+/// Watched servers and the arguments of every server profile, profile names are replaced by
+/// the address of their profile and queried with its modes and mode options
+fn watched_servers(
+    args: &DashboardArgs,
+    config: Option<&PathBuf>,
+) -> Result<(Vec<Server>, Vec<BaseArgs>)> {
+    let (profiles, settings): (Vec<_>, Vec<_>) = server_profiles(config)?
+        .into_iter()
+        .map(|(name, address, args)| ((name, address), args))
+        .unzip();
+    let names = if args.servers.is_empty() {
+        profiles.iter().map(|(name, _)| name.clone()).collect()
+    } else {
+        args.servers.clone()
+    };
+    let servers = names
+        .into_iter()
+        .map(|name| {
+            let profile = profiles.iter().position(|(profile, _)| *profile == name);
+            let address = profile.map_or(name.clone(), |index| profiles[index].1.clone());
+            Server {
+                name,
+                address,
+                profile,
+                pings: VecDeque::new(),
+                last: None,
+                updated: None,
+                querying: false,
+            }
+        })
+        .collect();
+    Ok((servers, settings))
+}
+
+async fn watch(
+    dashboard: &mut Dashboard<'_>,
+    engine: &QueryEngine<'_>,
+    modes: &[QueryMode],
+    profiles: &[(QueryEngine<'_>, &[QueryMode])],
+) -> Result<()> {
+    let _screen = Screen::enter()?;
+    let mut events = EventStream::new();
+    let mut refresh = interval(dashboard.interval);
+    refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Keeps the "updated" times moving between two refreshes
+    let mut redraw = interval(Duration::from_secs(1));
+    let mut due = VecDeque::new();
+    let mut pending = FuturesUnordered::new();
+    loop {
+        while pending.len() < SERVER_LIST_CONCURRENCY
+            && let Some(index) = due.pop_front()
+        {
+            let server: &mut Server = &mut dashboard.servers[index];
+            server.querying = true;
+            let (engine, modes) = match server.profile {
+                Some(profile) => (&profiles[profile].0, profiles[profile].1),
+                None => (engine, modes),
+            };
+            pending.push(probe(engine, modes, index, server.address.clone()));
+        }
+        dashboard.draw()?;
+
+        let mut action = Action::Continue;
+        tokio::select! {
+            _ = refresh.tick() => action = Action::Refresh,
+            _ = redraw.tick() => {}
+            Some((index, result)) = pending.next(), if !pending.is_empty() => {
+                dashboard.update(index, result).await;
+            }
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => action = dashboard.key(key).await,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => action = Action::Quit,
+            },
+        }
+        match action {
+            Action::Continue => {}
+            Action::Refresh => {
+                for (index, server) in dashboard.servers.iter().enumerate() {
+                    if !server.querying && !due.contains(&index) {
+                        due.push_back(index);
+                    }
+                }
+            }
+            Action::Quit => return Ok(()),
+        }
+    }
+}
+
+/// Watch servers until quit, the exit code reflects the last answer of every server
+pub async fn run_dashboard(
+    args: &DashboardArgs,
+    config: Option<&PathBuf>,
+    engine: &QueryEngine<'_>,
+    modes: &[QueryMode],
+    analyzers: &AnalyzerTools<'_>,
+    no_color: bool,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    let (servers, settings) = match watched_servers(args, config) {
+        Ok((servers, _)) if servers.is_empty() => {
+            log::error!("No server to watch, give addresses or add server profiles to the config");
+            outcome.failure(FailureKind::InvalidArgument);
+            return outcome;
+        }
+        Ok(watched) => watched,
+        Err(e) => {
+            log::error!("{}", e);
+            outcome.failure(local_failure_kind(&e));
+            return outcome;
+        }
+    };
+    let mut dashboard = Dashboard {
+        servers,
+        selected: 0,
+        view: View::Table,
+        detail: vec![],
+        interval: args.interval,
+        analyzers,
+        no_color,
+    };
+    let profiles = settings
+        .iter()
+        .map(|args| (init_query_engine(&args.mode_args), args.mode.as_slice()))
+        .collect::<Vec<_>>();
+    if let Err(e) = watch(&mut dashboard, engine, modes, &profiles).await {
+        log::error!("Dashboard failed: {}", e);
+        outcome.failure(FailureKind::LocalIo);
+        return outcome;
+    }
+    for server in &dashboard.servers {
+        match &server.last {
+            Some((_, Ok(_))) => outcome.success(),
+            Some((_, Err(e))) => outcome.error(e),
+            None => {}
+        }
+    }
+    outcome
+}
@@ -0,0 +1,57 @@
+//! Exit codes, kept stable so scripts can tell why a query failed.
+
+use crate::network::error::{FailureKind, failure_kind};
+use std::process::ExitCode;
+
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Every query succeeded
+  1  Partial success, some queries failed
+  2  Invalid arguments or config
+  3  Unreachable, nothing accepted the connection
+  4  Name resolution failed
+  5  Timed out
+  6  Protocol error, the answer was not a valid status
+  7  Proxy failure
+  8  Local file or terminal error";
+
+pub fn failure_code(kind: FailureKind) -> u8 {
+    match kind {
+        FailureKind::InvalidArgument => 2,
+        FailureKind::Unreachable => 3,
+        FailureKind::Resolve => 4,
+        FailureKind::Timeout => 5,
+        FailureKind::Protocol => 6,
+        FailureKind::Proxy => 7,
+        FailureKind::LocalIo => 8,
+    }
+}
+
+/// Collects query results, when nothing succeeded the furthest failure decides the code
+#[derive(Debug, Default)]
+pub struct Outcome {
+    succeeded: bool,
+    failed: Option<FailureKind>,
+}
+
+impl Outcome {
+    pub fn success(&mut self) {
+        self.succeeded = true;
+    }
+
+    pub fn failure(&mut self, kind: FailureKind) {
+        self.failed = self.failed.max(Some(kind));
+    }
+
+    pub fn error(&mut self, e: &anyhow::Error) {
+        self.failure(failure_kind(e));
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match (self.succeeded, self.failed) {
+            (_, None) => 0,
+            (true, Some(_)) => 1,
+            (false, Some(kind)) => failure_code(kind),
+        })
+    }
+}
@@ -0,0 +1,478 @@
+//! Append-only history of query outcomes, one JSON object per line, and reports over it.
+
+use crate::analyze::StatusPayload;
+use crate::mode::QueryMode;
+use crate::network::error::failure_kind;
+use crate::network::util::now_timestamp;
+use anyhow::{Result, anyhow};
+use clap::Args;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tokio::io::AsyncWriteExt;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const PERCENTILES: [usize; 3] = [50, 90, 99];
+
+static HISTORY_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Args, Debug)]
+pub struct HistorySettings {
+    /// Append every query outcome to this history file, for `report`
+    #[arg(long, global = true)]
+    pub record: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// History file written with `--record`
+    pub input: PathBuf,
+    /// Only records from this UTC time on, as `2024-05-01` or `2024-05-01T18:30`
+    #[arg(long, value_parser = parse_time)]
+    pub from: Option<i64>,
+    /// Only records before this UTC time, a date alone includes that whole day
+    #[arg(long, value_parser = parse_end_time)]
+    pub to: Option<i64>,
+    /// Only records of this target
+    #[arg(long)]
+    pub target: Option<String>,
+    /// Print the report as JSON instead of text
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn setup_history(settings: &HistorySettings) {
+    if let Some(path) = settings.record.as_ref()
+        && HISTORY_FILE.set(path.clone()).is_err()
+    {
+        log::warn!("History file is already set");
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn format_time(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(DAY_MS));
+    let minutes = timestamp.rem_euclid(DAY_MS) / 60_000;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+/// Milliseconds since the epoch of a UTC date, with an optional `THH:MM[:SS]` time
+fn parse_time(value: &str) -> Result<i64> {
+    let invalid = || anyhow!("Invalid time {}, expected YYYY-MM-DD[THH:MM[:SS]]", value);
+    let (date, time) = value.split_once(['T', ' ']).unwrap_or((value, ""));
+    let date = date
+        .split('-')
+        .map(str::parse::<i64>)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| invalid())?;
+    let [year, month, day] = date[..] else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return Err(invalid());
+    }
+    let mut seconds = 0;
+    if !time.is_empty() {
+        let time = time
+            .split(':')
+            .map(str::parse::<i64>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let (hour, minute, second) = match time[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return Err(invalid()),
+        };
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+        seconds = hour * 3600 + minute * 60 + second;
+    }
+    Ok(days_from_civil(year, month, day) * DAY_MS + seconds * 1000)
+}
+
+fn parse_end_time(value: &str) -> Result<i64> {
+    let time = parse_time(value)?;
+    let date_only = !value.contains(['T', ' ']);
+    Ok(if date_only { time + DAY_MS } else { time })
+}
+
+fn outcome_record(target: &str, mode: QueryMode, result: &Result<StatusPayload>) -> Value {
+    let mode = format!("{:?}", mode).to_lowercase();
+    match result {
+        Ok(payload) => json!({
+            "timestamp_ms": now_timestamp(),
+            "target": target,
+            "mode": mode,
+            "success": true,
+            "ping": payload.ping,
+            "player_count": payload.player_count,
+            "max_players": payload.max_players,
+            "version_name": payload.version_name,
+            "protocol": payload.protocol,
+        }),
+        Err(e) => json!({
+            "timestamp_ms": now_timestamp(),
+            "target": target,
+            "mode": mode,
+            "success": false,
+            "error_class": failure_kind(e).to_string(),
+            "error": e.to_string(),
+        }),
+    }
+}
+
+/// Append the outcome to the history file when `--record` is set
+pub async fn record(target: &str, mode: QueryMode, result: &Result<StatusPayload>) {
+    let Some(path) = HISTORY_FILE.get() else {
+        return;
+    };
+    let line = format!("{}\n", outcome_record(target, mode, result));
+    let append = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        // One write per line, so concurrent queries cannot interleave records
+        file.write_all(line.as_bytes()).await
+    };
+    if let Err(e) = append.await {
+        log::warn!("Failed to record history to {}: {}", path.display(), e);
+    }
+}
+
+fn percentile(sorted: &[i64], percent: usize) -> Option<i64> {
+    // Nearest rank, so every reported latency was actually measured
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+#[derive(Default)]
+struct Summary {
+    first: i64,
+    last: i64,
+    checks: usize,
+    successes: usize,
+    errors: BTreeMap<String, usize>,
+    pings: Vec<i64>,
+    players: Vec<i64>,
+    /// Every version seen and when, reduced to the changes once all records are in
+    versions: Vec<(i64, String)>,
+}
+
+impl Summary {
+    fn add(&mut self, record: &Value, timestamp: i64) {
+        // Concurrent writers and clock changes mean the file is not sorted by time
+        if self.checks == 0 {
+            (self.first, self.last) = (timestamp, timestamp);
+        }
+        self.first = self.first.min(timestamp);
+        self.last = self.last.max(timestamp);
+        self.checks += 1;
+        if !record["success"].as_bool().unwrap_or(false) {
+            let class = record["error_class"].as_str().unwrap_or("unknown");
+            *self.errors.entry(class.to_string()).or_default() += 1;
+            return;
+        }
+        self.successes += 1;
+        self.pings.extend(record["ping"].as_i64());
+        self.players.extend(record["player_count"].as_i64());
+        if let Some(version) = record["version_name"].as_str() {
+            self.versions.push((timestamp, version.to_string()));
+        }
+    }
+
+    /// Keep only the records where the version changed, in time order
+    fn finish(&mut self) {
+        self.versions.sort_by_key(|(timestamp, _)| *timestamp);
+        self.versions
+            .dedup_by(|(_, next), (_, previous)| next == previous);
+    }
+
+    fn uptime(&self) -> f64 {
+        self.successes as f64 * 100.0 / self.checks as f64
+    }
+
+    fn latency(&self) -> Vec<(usize, i64)> {
+        let mut sorted = self.pings.clone();
+        sorted.sort_unstable();
+        PERCENTILES
+            .iter()
+            .filter_map(|&p| Some((p, percentile(&sorted, p)?)))
+            .collect()
+    }
+
+    fn average_players(&self) -> Option<f64> {
+        (!self.players.is_empty())
+            .then(|| self.players.iter().sum::<i64>() as f64 / self.players.len() as f64)
+    }
+
+    fn to_json(&self, target: &str, mode: &str) -> Value {
+        let latency = self
+            .latency()
+            .into_iter()
+            .map(|(p, ping)| (format!("p{}", p), json!(ping)))
+            .collect::<serde_json::Map<_, _>>();
+        json!({
+            "target": target,
+            "mode": mode,
+            "first_ms": self.first,
+            "last_ms": self.last,
+            "checks": self.checks,
+            "successes": self.successes,
+            "uptime_percent": self.uptime(),
+            "errors": self.errors,
+            "latency_ms": latency,
+            "peak_players": self.players.iter().max(),
+            "average_players": self.average_players(),
+            "versions": self.versions.iter().map(|(t, v)| json!({"since_ms": t, "version": v})).collect::<Vec<_>>(),
+        })
+    }
+
+    fn log(&self, target: &str, mode: &str) {
+        log::info!(
+            "{} ({}): {} checks from {} to {}",
+            target,
+            mode,
+            self.checks,
+            format_time(self.first),
+            format_time(self.last)
+        );
+        let errors = self
+            .errors
+            .iter()
+            .map(|(class, count)| format!("{} {}", class, count))
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            log::info!("  Uptime: {:.2}%", self.uptime());
+        } else {
+            log::info!(
+                "  Uptime: {:.2}% ({} failed: {})",
+                self.uptime(),
+                self.checks - self.successes,
+                errors.join(", ")
+            );
+        }
+        let latency = self.latency();
+        if !latency.is_empty() {
+            let latency = latency
+                .iter()
+                .map(|(p, ping)| format!("p{} {}ms", p, ping))
+                .collect::<Vec<_>>();
+            log::info!("  Latency: {}", latency.join(", "));
+        }
+        if let (Some(peak), Some(average)) = (self.players.iter().max(), self.average_players()) {
+            log::info!("  Players: peak {}, average {:.1}", peak, average);
+        }
+        if !self.versions.is_empty() {
+            log::info!("  Versions:");
+            for (since, version) in &self.versions {
+                log::info!("    {}  {}", format_time(*since), version);
+            }
+        }
+    }
+}
+
+/// Summaries of the records in the requested range, sorted by target and mode
+async fn summarize(args: &ReportArgs) -> Result<BTreeMap<(String, String), Summary>> {
+    let text = tokio::fs::read_to_string(&args.input).await?;
+    let mut summaries: BTreeMap<(String, String), Summary> = BTreeMap::new();
+    let mut invalid = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let Ok(record) = serde_json::from_str::<Value>(line) else {
+            invalid += 1;
+            continue;
+        };
+        let (Some(timestamp), Some(target), Some(mode)) = (
+            record["timestamp_ms"].as_i64(),
+            record["target"].as_str(),
+            record["mode"].as_str(),
+        ) else {
+            invalid += 1;
+            continue;
+        };
+        if args.from.is_some_and(|from| timestamp < from)
+            || args.to.is_some_and(|to| timestamp >= to)
+            || args.target.as_ref().is_some_and(|t| t != target)
+        {
+            continue;
+        }
+        summaries
+            .entry((target.to_string(), mode.to_string()))
+            .or_default()
+            .add(&record, timestamp);
+    }
+    if invalid > 0 {
+        // A line cut short by a crash while appending is expected, anything more is worth a look
+        log::warn!("Skipped {} invalid history lines", invalid);
+    }
+    summaries.values_mut().for_each(Summary::finish);
+    Ok(summaries)
+}
+
+/// Report over the history file, false when no record matched
+pub async fn report(args: &ReportArgs) -> Result<bool> {
+    let summaries = summarize(args).await?;
+    if summaries.is_empty() {
+        return Ok(false);
+    }
+    if args.json {
+        let report = summaries
+            .iter()
+            .map(|((target, mode), summary)| summary.to_json(target, mode))
+            .collect::<Vec<_>>();
+        // Printed as is, log prefixes would break the JSON
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for ((target, mode), summary) in &summaries {
+            summary.log(target, mode);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_days_round_trip() {
+        let cases = [
+            ((1970, 1, 1), 0),
+            ((1969, 12, 31), -1),
+            ((2000, 2, 29), 11016),
+            ((2000, 3, 1), 11017),
+            ((2024, 2, 29), 19782),
+            ((2100, 2, 28), 47540),
+            ((2100, 3, 1), 47541),
+        ];
+        for ((year, month, day), days) in cases {
+            assert_eq!(
+                days_from_civil(year, month, day),
+                days,
+                "{}-{}-{}",
+                year,
+                month,
+                day
+            );
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        // Every day of a 400-year cycle maps back to itself
+        for days in -1000..146097 {
+            let (year, month, day) = civil_from_days(days);
+            assert!((1..=days_in_month(year, month)).contains(&day));
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn month_lengths() {
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2023, 4), 30);
+        assert_eq!(days_in_month(2023, 12), 31);
+    }
+
+    #[test]
+    fn dates_and_times() {
+        let day = days_from_civil(2024, 5, 1) * DAY_MS;
+        assert_eq!(parse_time("2024-05-01").unwrap(), day);
+        assert_eq!(parse_time("2024-05-01T18:30").unwrap(), day + 66_600_000);
+        assert_eq!(parse_time("2024-05-01 18:30:15").unwrap(), day + 66_615_000);
+        assert_eq!(parse_time("2000-02-29").unwrap(), 11016 * DAY_MS);
+        assert_eq!(format_time(day + 66_600_000), "2024-05-01 18:30");
+        for invalid in [
+            "2024-5",
+            "2024-13-01",
+            "2024-00-10",
+            "2023-02-29",
+            "2100-02-29",
+            "2024-04-31",
+            "2024-05-01T24:00",
+            "2024-05-01T12",
+            "2024-05-01T12:60",
+            "yesterday",
+        ] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn end_of_day() {
+        // A date alone covers the whole day, a time is taken as is
+        assert_eq!(
+            parse_end_time("2024-02-29").unwrap(),
+            parse_time("2024-03-01").unwrap()
+        );
+        assert_eq!(
+            parse_end_time("2024-12-31").unwrap(),
+            parse_time("2025-01-01").unwrap()
+        );
+        assert_eq!(
+            parse_end_time("2024-05-01T18:30").unwrap(),
+            parse_time("2024-05-01T18:30").unwrap()
+        );
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&[7], 50), Some(7));
+        assert_eq!(percentile(&[7], 99), Some(7));
+        let sorted = (1..=20).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 50), Some(10));
+        assert_eq!(percentile(&sorted, 95), Some(19));
+        assert_eq!(percentile(&sorted, 96), Some(20));
+        assert_eq!(percentile(&sorted, 100), Some(20));
+        assert_eq!(percentile(&sorted, 0), Some(1));
+        assert_eq!(percentile(&[1, 2], 50), Some(1));
+        assert_eq!(percentile(&[1, 2], 51), Some(2));
+    }
+}
@@ -1,6 +1,11 @@
 use clap::ValueEnum;
 use colored::Colorize;
 use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
+use std::sync::{Arc, Mutex};
+
+tokio::task_local! {
+    static BUFFER: Arc<Mutex<Vec<String>>>;
+}
 
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, ValueEnum)]
 pub enum LogLevel {
@@ -58,13 +63,49 @@ impl log::Log for SimpleLogger {
                     Level::Error => print.red(),
                 }
             };
-            println!("{}", colored);
+            let line = colored.to_string();
+            if BUFFER
+                .try_with(|buffer| buffer.lock().unwrap().push(line.clone()))
+                .is_err()
+            {
+                println!("{}", line);
+            }
         }
     }
 
     fn flush(&self) {}
 }
 
+/// Run a future while holding back its log lines, so concurrent tasks can be printed in order
+pub async fn buffered<F: Future>(future: F) -> (F::Output, Vec<String>) {
+    BUFFER
+        .scope(Arc::default(), async {
+            let output = future.await;
+            (
+                output,
+                BUFFER.with(|buffer| std::mem::take(&mut *buffer.lock().unwrap())),
+            )
+        })
+        .await
+}
+
+/// Keep logging into the buffer of the caller, task locals are not inherited by spawned tasks
+pub fn inherit<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let buffer = BUFFER.try_with(Arc::clone).ok();
+    async move {
+        match buffer {
+            Some(buffer) => BUFFER.scope(buffer, future).await,
+            None => future.await,
+        }
+    }
+}
+
+pub fn flush(lines: Vec<String>) {
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
 pub fn init(level: LogLevel, no_color: bool) -> Result<(), SetLoggerError> {
     log::set_boxed_logger(Box::new(SimpleLogger { level, no_color }))
         .map(|()| log::set_max_level(LevelFilter::Trace))
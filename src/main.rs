@@ -1,27 +1,58 @@
+#![allow(clippy::upper_case_acronyms)]
+
 mod analyze;
+mod config;
+mod dashboard;
+mod exit;
+mod history;
 mod logger;
 mod mode;
 mod network;
+mod run;
 
-use crate::analyze::{AnalyzerArgs, init_analyzer_tools, sanitize_analyzer_args};
-use crate::mode::{ModeArgs, init_query_engine};
-use crate::network::connection::{setup_proxy, ProxySettings};
-use clap::Parser;
+use crate::analyze::crossplay::crossplay_report;
+use crate::analyze::diff::{DiffArgs, diff_status};
+use crate::analyze::snapshot::read_snapshot;
+use crate::analyze::{
+    AnalyzerArgs, AnalyzerTools, StatusPayload, init_analyzer_tools, sanitize_analyzer_args,
+};
+use crate::dashboard::{DashboardArgs, run_dashboard};
+use crate::exit::{EXIT_CODES_HELP, Outcome, failure_code};
+use crate::history::{HistorySettings, ReportArgs, setup_history};
+use crate::mode::bedrock::sanitize_bedrock_args;
+use crate::mode::discover::{DiscoverArgs, Discovered, LanWorld, discover_bedrock, discover_java};
+use crate::mode::java::query_target;
+use crate::mode::servers_dat::{ServersDatArgs, read_entries, write_entries};
+use crate::mode::{ModeArgs, QueryEngine, init_query_engine};
+use crate::network::connection::{ProxySettings, sanitize_proxy_settings, setup_proxy};
+use crate::network::error::{FailureKind, failure_kind, local_failure_kind};
+use crate::network::proxy_protocol::{ProxyProtocolSettings, setup_proxy_protocol};
+use crate::network::util::{TimeoutSettings, setup_timeouts};
+use crate::run::{SERVER_LIST_CONCURRENCY, failure_table, query_mode, query_modes};
+use anyhow::Result;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use futures_util::StreamExt;
+use futures_util::future::OptionFuture;
+use futures_util::stream::FuturesUnordered;
 use logger::LogLevel;
 use mode::QueryMode;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 /// A tool for pinging Minecraft servers
 #[derive(Debug, Parser)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true, after_help = EXIT_CODES_HELP)]
 struct BaseArgs {
     /// Address to query
-    #[arg()]
-    address: String,
+    #[arg(required = true)]
+    address: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Query mode
     #[arg(short, long, value_parser, value_delimiter = ',', num_args = 1.., default_values = ["java", "bedrock"])]
     mode: Vec<QueryMode>,
-    /// Use all modes in `mode` option instead of returning when one mode succeed
+    /// Report every mode in `mode` option instead of returning when one mode succeed
     #[arg(long)]
     run_all_modes: bool,
 
@@ -32,20 +63,44 @@ struct BaseArgs {
 
     #[command(flatten)]
     proxy_settings: ProxySettings,
+    #[command(flatten)]
+    proxy_protocol_settings: ProxyProtocolSettings,
+    #[command(flatten)]
+    timeout_settings: TimeoutSettings,
+    #[command(flatten)]
+    history_settings: HistorySettings,
 
     /// Log level for output
-    #[arg(short, long, default_value = "info")]
+    #[arg(short, long, default_value = "info", global = true)]
     log_level: LogLevel,
     /// Shortcut for `--log-level trace`, enable all outputs
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
     /// Shortcut for `--log-level quiet`, disable all outputs
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     quiet: bool,
 
     /// Do not colorize all outputs
-    #[arg(long)]
+    #[arg(long, global = true)]
     no_color: bool,
+
+    /// Config file with option defaults and server profiles [default: $XDG_CONFIG_HOME/mcping/config.toml]
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Find servers on the local network instead of querying an address
+    Discover(DiscoverArgs),
+    /// Query every entry of a client server list
+    ServersDat(ServersDatArgs),
+    /// Watch servers in a live table, refreshed on an interval
+    Dashboard(DashboardArgs),
+    /// Show what changed between two snapshots, or a snapshot and the server now
+    Diff(DiffArgs),
+    /// Summarize uptime, latency, players and versions from a `--record` history file
+    Report(ReportArgs),
 }
 
 fn sanitize_main_args(args: &mut BaseArgs) {
@@ -58,38 +113,404 @@ fn sanitize_main_args(args: &mut BaseArgs) {
     if std::env::var("NO_COLOR").is_ok() {
         args.no_color = true;
     }
-    if let Ok(all_proxy) = std::env::var("ALL_PROXY") {
-        args.proxy_settings.proxy = Some(all_proxy);
+    sanitize_proxy_settings(&mut args.proxy_settings);
+    sanitize_bedrock_args(args);
+}
+
+async fn report(
+    mode: QueryMode,
+    (result, lines): (Result<StatusPayload>, Vec<String>),
+    analyzers: &AnalyzerTools<'_>,
+    outcome: &mut Outcome,
+) {
+    logger::flush(lines);
+    match result {
+        Ok(payload) => {
+            log::info!("Query successful use mode {:?}", mode);
+            analyzers.analyze(&payload).await;
+            outcome.success();
+        }
+        Err(e) => {
+            log::error!("Failed for mode {:?}: {}", mode, e);
+            failure_table(&e);
+            outcome.error(&e);
+        }
     }
 }
 
+async fn report_crossplay(
+    modes: &[QueryMode],
+    results: Vec<Option<(Result<StatusPayload>, Vec<String>)>>,
+    analyzers: &AnalyzerTools<'_>,
+    outcome: &mut Outcome,
+) {
+    // Cancelled modes leave empty slots, pair before dropping them so results keep their mode
+    let results = modes
+        .iter()
+        .zip(results)
+        .filter_map(|(mode, result)| result.map(|result| (*mode, result)))
+        .collect::<Vec<_>>();
+    let find = |family: fn(&QueryMode) -> bool| {
+        results
+            .iter()
+            .position(|(mode, (result, _))| result.is_ok() && family(mode))
+    };
+    let (Some(java), Some(bedrock)) = (
+        find(QueryMode::is_java_family),
+        find(|mode| *mode == QueryMode::BEDROCK),
+    ) else {
+        for (mode, result) in results {
+            report(mode, result, analyzers, outcome).await;
+        }
+        return;
+    };
+
+    let mut payloads = vec![];
+    for (index, (mode, result)) in results.into_iter().enumerate() {
+        if index == java || index == bedrock {
+            logger::flush(result.1);
+            log::info!("Query successful use mode {:?}", mode);
+            payloads.extend(result.0.ok());
+            outcome.success();
+        } else {
+            report(mode, result, analyzers, outcome).await;
+        }
+    }
+    // Payloads keep the mode list order, swap them back if Bedrock came first
+    if bedrock < java {
+        payloads.swap(0, 1);
+    }
+    crossplay_report(&payloads[0], &payloads[1]);
+}
+
+async fn report_java_worlds(
+    found: Result<Vec<LanWorld>>,
+    analyzers: &AnalyzerTools<'_>,
+    protocol: i32,
+    outcome: &mut Outcome,
+) {
+    match found {
+        Ok(found) if found.is_empty() => {
+            log::warn!("No Java LAN world was announced on the local network");
+            outcome.failure(FailureKind::Unreachable);
+        }
+        Ok(found) => {
+            for world in found {
+                log::info!("Found Java LAN world at {}", world.addr);
+                analyzers.render_motd(&world.motd);
+                let host = world.addr.ip().to_string();
+                match query_target(&host, world.addr.port(), protocol).await {
+                    Ok(status) => {
+                        analyzers.analyze(&status).await;
+                        outcome.success();
+                    }
+                    Err(e) => {
+                        log::error!("Failed to query {}: {}", world.addr, e);
+                        outcome.error(&e);
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Java LAN discovery failed: {}", e);
+            outcome.error(&e);
+        }
+    }
+}
+
+async fn report_bedrock_servers(
+    found: Result<Vec<Discovered>>,
+    analyzers: &AnalyzerTools<'_>,
+    outcome: &mut Outcome,
+) {
+    match found {
+        Ok(found) if found.is_empty() => {
+            log::warn!("No Bedrock server answered on the local network");
+            outcome.failure(FailureKind::Unreachable);
+        }
+        Ok(found) => {
+            for server in found {
+                log::info!("Found Bedrock server at {}", server.addr);
+                analyzers.analyze(&server.status).await;
+            }
+            outcome.success();
+        }
+        Err(e) => {
+            log::error!("Bedrock discovery failed: {}", e);
+            outcome.error(&e);
+        }
+    }
+}
+
+async fn run_discover(
+    args: &DiscoverArgs,
+    analyzers: &AnalyzerTools<'_>,
+    protocol: i32,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    if !args.bedrock && !args.java {
+        log::error!("Nothing to discover, select --java and/or --bedrock");
+        outcome.failure(FailureKind::InvalidArgument);
+        return outcome;
+    }
+    // Both listen for the same window, so they run together and are reported one after another
+    let (java, bedrock) = tokio::join!(
+        OptionFuture::from(
+            args.java
+                .then(|| discover_java(args.window, args.interface))
+        ),
+        OptionFuture::from(
+            args.bedrock
+                .then(|| discover_bedrock(args.window, args.interface))
+        ),
+    );
+    if let Some(found) = java {
+        report_java_worlds(found, analyzers, protocol, &mut outcome).await;
+    }
+    if let Some(found) = bedrock {
+        report_bedrock_servers(found, analyzers, &mut outcome).await;
+    }
+    outcome
+}
+
+async fn run_servers_dat(
+    args: &ServersDatArgs,
+    engine: &QueryEngine<'_>,
+    modes: &[QueryMode],
+    analyzers: &AnalyzerTools<'_>,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    let mut entries = match read_entries(&args.input, args.from_list).await {
+        Ok(entries) if entries.is_empty() => {
+            log::warn!("No server in {}", args.input.display());
+            outcome.failure(FailureKind::InvalidArgument);
+            return outcome;
+        }
+        Ok(entries) => entries,
+        Err(e) => {
+            log::error!("Failed to read {}: {}", args.input.display(), e);
+            outcome.failure(local_failure_kind(&e));
+            return outcome;
+        }
+    };
+
+    // Entries are queried a few at a time and reported in list order
+    let mut answers = futures_util::stream::iter(&entries)
+        .map(|entry| logger::buffered(query_modes(engine, modes, entry.ip())))
+        .buffered(SERVER_LIST_CONCURRENCY);
+    let mut summary = vec![];
+    let mut favicons = vec![];
+    let mut index = 0;
+    while let Some(((mode, result), lines)) = answers.next().await {
+        let entry = &entries[index];
+        index += 1;
+        log::info!("Server list entry '{}' ({})", entry.name(), entry.ip());
+        let status = match &result {
+            Ok(payload) => format!(
+                "{:?} {}/{} {}ms",
+                mode,
+                payload.player_count.unwrap_or_default(),
+                payload.max_players.unwrap_or_default(),
+                payload.ping
+            ),
+            Err(_) => "failed".to_string(),
+        };
+        summary.push((entry.name().to_string(), entry.ip().to_string(), status));
+        favicons.push(result.as_ref().ok().and_then(|p| p.favicon.clone()));
+        report(mode, (result, lines), analyzers, &mut outcome).await;
+    }
+    drop(answers);
+
+    log::info!("Server list summary:");
+    for (name, ip, status) in summary {
+        log::info!("  {:24} {:32} {}", name, ip, status);
+    }
+
+    if let Some(export) = &args.export {
+        for (entry, favicon) in entries.iter_mut().zip(favicons) {
+            if let Some(favicon) = favicon
+                && !entry.set_icon(&favicon)
+            {
+                log::debug!("Favicon of {} cannot be stored, icon kept", entry.ip());
+            }
+        }
+        match write_entries(export, &entries).await {
+            Ok(()) => log::info!("Exported {} servers to {}", entries.len(), export.display()),
+            Err(e) => {
+                log::error!("Failed to write {}: {}", export.display(), e);
+                outcome.failure(local_failure_kind(&e));
+            }
+        }
+    }
+    outcome
+}
+
+async fn run_diff(args: &DiffArgs, engine: &QueryEngine<'_>, modes: &[QueryMode]) -> Outcome {
+    let mut outcome = Outcome::default();
+    let old = match read_snapshot(&args.old).await {
+        Ok(old) => old,
+        Err(e) => {
+            log::error!("Failed to read snapshot {}: {}", args.old.display(), e);
+            outcome.failure(local_failure_kind(&e));
+            return outcome;
+        }
+    };
+    let new = if Path::new(&args.new).is_file() {
+        match read_snapshot(Path::new(&args.new)).await {
+            Ok(new) => new,
+            Err(e) => {
+                log::error!("Failed to read snapshot {}: {}", args.new, e);
+                outcome.failure(local_failure_kind(&e));
+                return outcome;
+            }
+        }
+    } else {
+        match query_modes(engine, modes, &args.new).await {
+            (_, Ok(new)) => new,
+            (mode, Err(e)) => {
+                log::error!("Failed for mode {:?}: {}", mode, e);
+                failure_table(&e);
+                outcome.error(&e);
+                return outcome;
+            }
+        }
+    };
+
+    let changes = diff_status(&old, &new);
+    if changes.is_empty() {
+        log::info!("No changes");
+    }
+    for change in changes {
+        log::info!("{}", change);
+    }
+    outcome.success();
+    outcome
+}
+
+async fn run_report(args: &ReportArgs) -> Outcome {
+    let mut outcome = Outcome::default();
+    match history::report(args).await {
+        Ok(true) => outcome.success(),
+        Ok(false) => {
+            log::error!("No records in {} match", args.input.display());
+            outcome.failure(FailureKind::InvalidArgument);
+        }
+        Err(e) => {
+            log::error!("Failed to read history {}: {}", args.input.display(), e);
+            outcome.failure(local_failure_kind(&e));
+        }
+    }
+    outcome
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> ExitCode {
-    let mut args = BaseArgs::parse();
+    let mut args = config::parse_args()
+        .unwrap_or_else(|e| BaseArgs::command().error(ErrorKind::InvalidValue, e).exit());
     sanitize_main_args(&mut args);
     logger::init(args.log_level, args.no_color).expect("Failed to initialize logger");
-    setup_proxy(&args.proxy_settings);
+    setup_timeouts(&args.timeout_settings);
+    if let Err(e) = setup_proxy(&args.proxy_settings) {
+        log::error!("{}", e);
+        return ExitCode::from(failure_code(failure_kind(&e)));
+    }
+    setup_proxy_protocol(&args.proxy_protocol_settings);
+    setup_history(&args.history_settings);
     sanitize_analyzer_args(&mut args);
 
     let engine = init_query_engine(&args.mode_args);
-    let analyzers = init_analyzer_tools(&args.analyzer_args);
-
-    let mut fail_count = 0;
-    for mode in args.mode {
-        match engine.query(mode, &args.address).await {
-            Ok(payload) => {
-                log::info!("Query successful use mode {:?}", mode);
-                analyzers.analyze(&payload).await;
-                if !args.run_all_modes {
-                    break;
-                }
-            }
-            Err(e) => {
-                fail_count += 1;
-                log::error!("Failed for mode {:?}: {}", mode, e);
+    let analyzers = init_analyzer_tools(&args.analyzer_args, args.mode_args.client_protocol());
+    if let Some(Command::Discover(discover)) = &args.command {
+        return run_discover(discover, &analyzers, args.mode_args.client_protocol())
+            .await
+            .exit_code();
+    }
+    if let Some(Command::ServersDat(servers)) = &args.command {
+        return run_servers_dat(servers, &engine, &args.mode, &analyzers)
+            .await
+            .exit_code();
+    }
+    if let Some(Command::Dashboard(dashboard)) = &args.command {
+        return run_dashboard(
+            dashboard,
+            args.config.as_ref(),
+            &engine,
+            &args.mode,
+            &analyzers,
+            args.no_color,
+        )
+        .await
+        .exit_code();
+    }
+    if let Some(Command::Diff(diff)) = &args.command {
+        return run_diff(diff, &engine, &args.mode).await.exit_code();
+    }
+    if let Some(Command::Report(report)) = &args.command {
+        return run_report(report).await.exit_code();
+    }
+    // Only a subcommand lifts the requirement of an address
+    let address = args.address.as_deref().unwrap_or_default();
+
+    let mut pending = args
+        .mode
+        .iter()
+        .enumerate()
+        .map(|(index, &mode)| {
+            let query = query_mode(&engine, mode, address);
+            async move { (index, logger::buffered(query).await) }
+        })
+        .collect::<FuturesUnordered<_>>();
+
+    // Java and Bedrock answers are merged into one report, so nothing is printed before both are known
+    let crossplay = args.run_all_modes
+        && args.mode.contains(&QueryMode::BEDROCK)
+        && args.mode.iter().any(QueryMode::is_java_family);
+
+    // Results are reported in the order of the mode list, whatever order they finish in
+    let mut results = args.mode.iter().map(|_| None).collect::<Vec<_>>();
+    let mut reported = 0;
+    let mut outcome = Outcome::default();
+    let mut winner = None;
+    while let Some((index, result)) = pending.next().await {
+        let succeeded = result.0.is_ok();
+        results[index] = Some(result);
+        if succeeded && !args.run_all_modes {
+            winner = Some(index);
+            break;
+        }
+        if crossplay {
+            continue;
+        }
+        while let Some(result) = results.get_mut(reported).and_then(Option::take) {
+            report(args.mode[reported], result, &analyzers, &mut outcome).await;
+            reported += 1;
+        }
+    }
+    if crossplay {
+        report_crossplay(
+            &args.mode,
+            std::mem::take(&mut results),
+            &analyzers,
+            &mut outcome,
+        )
+        .await;
+    }
+    // Cancel the modes still running once one has succeeded
+    drop(pending);
+    if let Some(winner) = winner {
+        for (result, mode) in results
+            .into_iter()
+            .zip(&args.mode)
+            .take(winner + 1)
+            .skip(reported)
+        {
+            if let Some(result) = result {
+                report(*mode, result, &analyzers, &mut outcome).await;
             }
         }
+        // Modes tried before the one that answered were only fallbacks
+        return ExitCode::SUCCESS;
     }
 
-    ExitCode::from(fail_count)
+    outcome.exit_code()
 }
@@ -0,0 +1,79 @@
+use crate::analyze::StatusPayload;
+use crate::mode::QueryModeHandler;
+use crate::mode::bedrock::{BedrockModeArgs, BedrockQuery};
+use crate::mode::java::{JavaModeArgs, JavaQuery};
+#[cfg(feature = "ping-legacy")]
+use crate::mode::legacy::{LegacyDialect, LegacyQuery};
+use crate::network::error::Failures;
+#[cfg(feature = "ping-legacy")]
+use crate::network::error::{FailureKind, failure_kind};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+pub struct AutoQuery<'a> {
+    java: JavaQuery<'a>,
+    #[cfg(feature = "ping-legacy")]
+    legacy: LegacyQuery<'a>,
+    bedrock: BedrockQuery<'a>,
+}
+
+impl AutoQuery<'_> {
+    async fn query_java_family(&self, addr: &str) -> Result<StatusPayload> {
+        match self.java.do_query(addr).await {
+            Ok(status) => Ok(status),
+            // A legacy server kicks with 0xFF or answers a frame Java cannot decode, anything else
+            // would fail the same way again and only cost another timeout
+            #[cfg(feature = "ping-legacy")]
+            Err(e) if failure_kind(&e) == FailureKind::Protocol => {
+                log::debug!("Java status query failed, falling back to legacy: {}", e);
+                self.legacy.do_query(addr).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl QueryModeHandler for AutoQuery<'_> {
+    async fn do_query(&self, addr: &str) -> Result<StatusPayload> {
+        let (java, bedrock) =
+            tokio::join!(self.query_java_family(addr), self.bedrock.do_query(addr));
+
+        let mut families = vec![];
+        let mut found = None;
+        let mut failures = Failures::default();
+        for (family, res) in [("java", java), ("bedrock", bedrock)] {
+            match res {
+                Ok(status) => {
+                    families.push(format!("{:?}", status.mode).to_lowercase());
+                    found.get_or_insert(status);
+                }
+                Err(e) => {
+                    log::debug!("Auto detection probe failed: {}", e);
+                    failures.record(family, e);
+                }
+            }
+        }
+
+        let Some(mut status) = found else {
+            return Err(failures.into_error("No protocol detected"));
+        };
+        let extra = status.full_extra.get_or_insert_with(|| json!({}));
+        if let Value::Object(map) = extra {
+            map.insert("protocol_families".to_string(), json!(families));
+        }
+        Ok(status)
+    }
+}
+
+impl AutoQuery<'_> {
+    pub fn new<'a>(java: &'a JavaModeArgs, bedrock: &'a BedrockModeArgs) -> AutoQuery<'a> {
+        AutoQuery {
+            java: JavaQuery::new(java),
+            #[cfg(feature = "ping-legacy")]
+            legacy: LegacyQuery::new(java, LegacyDialect::Auto),
+            bedrock: BedrockQuery::new(bedrock),
+        }
+    }
+}
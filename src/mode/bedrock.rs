@@ -1,111 +1,339 @@
 use crate::analyze::{MotdInfo, StatusPayload};
+use crate::logger;
 use crate::mode::QueryMode::BEDROCK;
 use crate::mode::QueryModeHandler;
 use crate::network::connection::{ProxyableUdpSocket, UdpTarget, udp_socket};
+use crate::network::error::{Failures, QueryError};
 use crate::network::resolve::sanitize_addr;
-use crate::network::util::{generic_timeout, now_timestamp};
+use crate::network::schema::codec::{
+    MAGIC_HIGH, MAGIC_LOW, MAX_DATAGRAM_SIZE, decode_bedrock_status, decode_unconnected_pong,
+};
+use crate::network::util::{Timings, clock_skew, now_timestamp, parse_duration, read_timeout};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use bytes::{Buf, BufMut, BytesMut};
-use serde_json::json;
+use bytes::BufMut;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory};
+use serde_json::{Value, json};
 use std::time::Duration;
 use tokio::task::JoinSet;
+use tokio::time::{Instant, timeout_at};
 
-const MAGIC_HIGH: u64 = 0x00ffff00fefefefeu64;
-const MAGIC_LOW: u64 = 0xfdfdfdfd12345678u64;
+/// Unconnected Ping is `0x01`, `0x02` only asks servers with open connections to answer
+pub const UNCONNECTED_PING: u8 = 0x01;
+pub const UNCONNECTED_PING_OPEN_CONNECTIONS: u8 = 0x02;
 
-async fn single_ip_check(addr: &UdpTarget, socket: ProxyableUdpSocket) -> Result<StatusPayload> {
-    let timeout_time = Duration::from_secs(5);
-
-    let timestamp = now_timestamp();
-    let mut packet = Vec::from([1u8]);
+pub fn ping_packet(id: u8, timestamp: i64) -> Vec<u8> {
+    let mut packet = Vec::from([id]);
     packet.put_i64(timestamp);
     packet.put_u64(MAGIC_HIGH);
     packet.put_u64(MAGIC_LOW);
     packet.put_u16(0);
-    socket.send_to(&packet, addr).await?;
-    log::trace!("Sent Unconnected Ping packet");
+    packet
+}
+
+/// Shorter intervals would flood the server with pings until the read timeout
+const MIN_PING_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Args, Debug)]
+pub struct BedrockModeArgs {
+    /// Interval for resending Bedrock pings until one is answered or the read timeout passes
+    #[arg(long, value_parser = parse_ping_interval, default_value = "1s")]
+    pub bedrock_ping_interval: Duration,
+    /// Re-ping the advertised IPv4 and IPv6 game ports and report whether they answer with the same server guid
+    #[arg(long)]
+    pub follow_ports: bool,
+}
 
-    let mut recv_buf = [0u8; 1024];
-    let recv = generic_timeout(timeout_time, socket.recv_from(&mut recv_buf), "Recv").await?;
-    log::trace!("Received response from {}", addr);
+fn parse_ping_interval(value: &str) -> Result<Duration> {
+    let interval = parse_duration(value)?;
+    if interval < MIN_PING_INTERVAL {
+        return Err(anyhow!(
+            "Interval must be at least {}ms",
+            MIN_PING_INTERVAL.as_millis()
+        ));
+    }
+    Ok(interval)
+}
 
-    let mut bytes = BytesMut::from(&recv_buf[..recv]);
-    if bytes.get_u8() != 0x1C {
-        return Err(anyhow!("Unexpected response byte"));
+/// A resend interval past the read timeout would never resend anything
+pub fn sanitize_bedrock_args(args: &crate::BaseArgs) {
+    let interval = args.mode_args.bedrock.bedrock_ping_interval;
+    let read_timeout = args.timeout_settings.read_timeout;
+    if interval > read_timeout {
+        crate::BaseArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--bedrock-ping-interval ({:?}) cannot be longer than --read-timeout ({:?})",
+                    interval, read_timeout
+                ),
+            )
+            .exit();
     }
+}
 
-    let server_clock = bytes.get_i64();
-    let server_guid = bytes.get_u64();
-    let magic_high = bytes.get_u64();
-    let magic_low = bytes.get_u64();
-    let str_len = bytes.get_u16();
+struct PingExchange {
+    recv: Vec<u8>,
+    sent_clock: i64,
+    round_trip: Duration,
+    /// Pings sent in total, and the one the pong answered
+    attempts: usize,
+    answered: usize,
+}
 
-    if magic_high != MAGIC_HIGH || magic_low != MAGIC_LOW {
-        return Err(anyhow!("Invalid magic number"));
+async fn unconnected_ping(
+    addr: &UdpTarget,
+    socket: &ProxyableUdpSocket,
+    interval: Duration,
+) -> Result<PingExchange> {
+    let deadline = Instant::now() + read_timeout();
+    let mut sent: Vec<(i64, Instant)> = vec![];
+    let mut next_send = Instant::now();
+    let mut recv_buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(QueryError::Timeout {
+                phase: format!("Waiting for a pong to {} pings", sent.len()),
+            }
+            .into());
+        }
+        if now >= next_send {
+            // Pongs are matched by the echoed timestamp, so every ping needs its own
+            let timestamp = now_timestamp().max(sent.last().map_or(i64::MIN, |(t, _)| t + 1));
+            socket
+                .send_to(&ping_packet(UNCONNECTED_PING, timestamp), addr)
+                .await?;
+            sent.push((timestamp, Instant::now()));
+            log::trace!("Sent Unconnected Ping packet #{}", sent.len());
+            next_send = now + interval;
+        }
+
+        let wake = next_send.min(deadline);
+        let Ok(recv) = timeout_at(wake, socket.recv_from(&mut recv_buf, addr)).await else {
+            continue;
+        };
+        let Some(len) = recv? else {
+            continue;
+        };
+        let recv = &recv_buf[..len];
+        let time = match decode_unconnected_pong(recv) {
+            Ok(pong) => pong.time,
+            Err(e) => {
+                log::debug!("Ignored invalid datagram from {}: {}", addr, e);
+                continue;
+            }
+        };
+        // Without the echoed time there is no telling which ping it answers, or if it answers us at all
+        let Some(answered) = sent.iter().position(|(t, _)| *t == time) else {
+            log::debug!("Ignored pong from {} echoing no sent ping: {}", addr, time);
+            continue;
+        };
+        let (sent_clock, sent_at) = sent[answered];
+        return Ok(PingExchange {
+            recv: recv.to_vec(),
+            sent_clock,
+            round_trip: sent_at.elapsed(),
+            attempts: sent.len(),
+            answered: answered + 1,
+        });
     }
-    if str_len as usize != recv - 35 {
-        return Err(anyhow!("Invalid string length"));
+}
+
+async fn single_ip_check(
+    addr: &UdpTarget,
+    socket: ProxyableUdpSocket,
+    interval: Duration,
+    timings: Timings,
+) -> Result<StatusPayload> {
+    let exchange = unconnected_ping(addr, &socket, interval).await?;
+    log::trace!(
+        "Received response from {} after {} pings",
+        addr,
+        exchange.attempts
+    );
+    let mut status = parse_pong(
+        &exchange.recv,
+        exchange.sent_clock,
+        exchange.round_trip,
+        timings,
+    )?;
+    if let Some(extra) = status.full_extra.as_mut() {
+        extra["ping_attempts"] = json!(exchange.attempts);
+        extra["ping_answered"] = json!(exchange.answered);
     }
+    Ok(status)
+}
 
-    let ping = now_timestamp() - server_clock;
-    let resp = String::from_utf8(bytes.to_vec())?;
+/// Build the status from a pong datagram answering a ping sent at `sent_clock`
+pub fn parse_pong(
+    recv: &[u8],
+    sent_clock: i64,
+    round_trip: Duration,
+    mut timings: Timings,
+) -> Result<StatusPayload> {
+    timings.round_trip = Some(round_trip);
+    timings.status_size = Some(recv.len());
+    let pong = decode_unconnected_pong(recv).map_err(QueryError::from)?;
+    let server_clock = pong.time;
+    let server_guid = pong.server_guid;
+
+    let ping = round_trip.as_millis() as i64;
+    let clock_skew = if server_clock == sent_clock {
+        None
+    } else {
+        log::debug!(
+            "Pong time {} does not echo sent {}",
+            server_clock,
+            sent_clock
+        );
+        clock_skew(sent_clock, round_trip, server_clock)
+    };
+    let resp = pong.server_id;
     log::trace!("Ping response: {}", resp);
 
-    let parts = resp.split(';').collect::<Vec<_>>();
-    if parts.len() < 9 {
-        return Err(anyhow!("Invalid response"));
-    }
+    let status = decode_bedrock_status(&resp).map_err(QueryError::from)?;
+    let motd = match status.level_name.as_ref() {
+        Some(level_name) => format!("{}\n{}", status.motd, level_name),
+        None => status.motd.clone(),
+    };
 
     Ok(StatusPayload {
         mode: BEDROCK,
         ping,
-        max_players: Some(parts[5].parse()?),
-        player_count: Some(parts[4].parse()?),
+        clock_skew,
+        max_players: Some(status.max_players),
+        player_count: Some(status.player_count),
         players: None,
-        motd: Some(MotdInfo::String(format!("{}\n{}", parts[1], parts[7]))),
-        protocol: Some(parts[2].parse()?),
-        version_name: Some(parts[3].to_string()),
+        motd: Some(MotdInfo::String(motd)),
+        protocol: Some(status.protocol),
+        version_name: Some(status.version.clone()),
         favicon: None,
-        full_extra: Some(json!({"server_guid": server_guid, "game_mode": parts[8].to_string()})),
+        full_extra: Some(json!({
+            "server_guid": server_guid,
+            "edition": status.edition,
+            "server_unique_id": status.server_unique_id,
+            "level_name": status.level_name,
+            "game_mode": status.game_mode,
+            "game_mode_id": status.game_mode_id,
+            "port_v4": status.port_v4,
+            "port_v6": status.port_v6,
+            "extra_fields": status.extra_fields,
+            "field_count": status.field_count,
+            "trailing_separator": status.trailing_separator,
+        })),
+        timings,
     })
 }
 
-async fn safe_ip_check(addr: UdpTarget, socket: ProxyableUdpSocket) -> Result<StatusPayload> {
-    match single_ip_check(&addr, socket).await {
-        Ok(status) => Ok(status),
-        Err(e) => Err(anyhow!("Protocol error in <{}>: {}", addr, e)),
+/// First answer from any resolved address of one family
+async fn ping_family(
+    host: &str,
+    port: u16,
+    ipv6: bool,
+    interval: Duration,
+) -> Result<StatusPayload> {
+    let socks = udp_socket(host, port, ipv6).await?;
+    let mut set = JoinSet::new();
+
+    for (target, socket, timings) in socks {
+        set.spawn(logger::inherit(async move {
+            let res = single_ip_check(&target, socket, interval, timings).await;
+            (target, res)
+        }));
+    }
+
+    let mut failures = Failures::default();
+    while let Some(join_res) = set.join_next().await {
+        if let Ok((target, res)) = join_res {
+            match res {
+                Ok(res) => return Ok(res),
+                Err(e) => {
+                    log::debug!("Attempt to <{}> failed: {}", target, e);
+                    failures.record(target, e);
+                }
+            }
+        }
     }
+
+    Err(failures.into_error("No server found"))
 }
 
-pub struct BedrockQuery;
+async fn follow_port(
+    host: &str,
+    family: &str,
+    port: Option<u16>,
+    ipv6: bool,
+    interval: Duration,
+) -> Option<Value> {
+    let port = port?;
+    log::debug!("Following advertised {} port {}", family, port);
+    let check = match ping_family(host, port, ipv6, interval).await {
+        Ok(answer) => json!({
+            "family": family,
+            "port": port,
+            "server_guid": answer.full_extra.as_ref().map(|x| x["server_guid"].clone()),
+            "ping": answer.ping,
+        }),
+        Err(e) => json!({
+            "family": family,
+            "port": port,
+            "error": e.to_string(),
+        }),
+    };
+    Some(check)
+}
+
+/// Ping the game ports a pong advertises, so split or misconfigured listeners show up
+async fn follow_ports(host: &str, port: u16, interval: Duration, status: &mut StatusPayload) {
+    let ping = status.ping;
+    let Some(extra) = status.full_extra.as_mut() else {
+        return;
+    };
+    let advertised = |key: &str| {
+        extra[key]
+            .as_u64()
+            .and_then(|p| u16::try_from(p).ok())
+            .filter(|p| *p != 0)
+    };
+    let (port_v4, port_v6) = (advertised("port_v4"), advertised("port_v6"));
+    // The answer we already have came from the pinged port over IPv4
+    let v4 = async {
+        match port_v4 {
+            Some(p) if p == port => Some(json!({
+                "family": "IPv4",
+                "port": p,
+                "server_guid": extra["server_guid"],
+                "ping": ping,
+            })),
+            _ => follow_port(host, "IPv4", port_v4, false, interval).await,
+        }
+    };
+    let v6 = follow_port(host, "IPv6", port_v6, true, interval);
+    let (v4, v6) = tokio::join!(v4, v6);
+    extra["followed_ports"] = json!([v4, v6].into_iter().flatten().collect::<Vec<_>>());
+}
+
+pub struct BedrockQuery<'a> {
+    args: &'a BedrockModeArgs,
+}
 
 #[async_trait]
-impl QueryModeHandler for BedrockQuery {
+impl QueryModeHandler for BedrockQuery<'_> {
     async fn do_query(&self, addr: &str) -> Result<StatusPayload> {
         let (host, port) = sanitize_addr(addr, 19132)?;
-        let socks = udp_socket(&host, port, Duration::new(5, 0)).await?;
-        let mut set = JoinSet::new();
-
-        for sock in socks {
-            set.spawn(safe_ip_check(sock.0, sock.1));
+        let interval = self.args.bedrock_ping_interval;
+        let mut status = ping_family(&host, port, false, interval).await?;
+        if self.args.follow_ports {
+            follow_ports(&host, port, interval, &mut status).await;
         }
-
-        while let Some(join_res) = set.join_next().await {
-            if let Ok(res) = join_res {
-                match res {
-                    Ok(res) => return Ok(res),
-                    Err(e) => log::warn!("{}", e),
-                }
-            }
-        }
-
-        Err(anyhow!("No server found"))
+        Ok(status)
     }
 }
 
-impl BedrockQuery {
-    pub fn new() -> BedrockQuery {
-        BedrockQuery {}
+impl BedrockQuery<'_> {
+    pub fn new(args: &'_ BedrockModeArgs) -> BedrockQuery<'_> {
+        BedrockQuery { args }
     }
 }
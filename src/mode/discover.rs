@@ -0,0 +1,179 @@
+use crate::analyze::StatusPayload;
+use crate::mode::bedrock::{
+    UNCONNECTED_PING, UNCONNECTED_PING_OPEN_CONNECTIONS, parse_pong, ping_packet,
+};
+use crate::network::schema::codec::{MAX_DATAGRAM_SIZE, decode_lan_announcement};
+use crate::network::util::{Timings, now_timestamp, parse_duration};
+use anyhow::Result;
+use clap::Args;
+use socket2::{Domain, InterfaceIndexOrAddress, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{Instant, timeout_at};
+
+const BEDROCK_PORT: u16 = 19132;
+/// All nodes on the local link, Bedrock clients announce worlds there on IPv6
+const BEDROCK_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+/// Java clients multicast "Open to LAN" announcements to this group
+const JAVA_LAN_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 2, 60);
+const JAVA_LAN_PORT: u16 = 4445;
+
+#[derive(Args, Debug)]
+pub struct DiscoverArgs {
+    /// Broadcast Bedrock Unconnected Pings on the local network
+    #[arg(long)]
+    pub bedrock: bool,
+    /// Listen for Java "Open to LAN" announcements
+    #[arg(long)]
+    pub java: bool,
+    /// How long to collect answers
+    #[arg(long, value_parser = parse_duration, default_value = "2s")]
+    pub window: Duration,
+    /// Index of the network interface used for multicast (as listed by `ip link`),
+    /// the system picks one by default which may not reach the wanted network on multi-homed hosts
+    #[arg(long, default_value_t = 0)]
+    pub interface: u32,
+}
+
+/// A server found on the local network
+pub struct Discovered {
+    pub addr: SocketAddr,
+    pub status: StatusPayload,
+}
+
+/// A Java world announced on the local network
+pub struct LanWorld {
+    pub addr: SocketAddr,
+    pub motd: String,
+}
+
+fn server_guid(status: &StatusPayload) -> Option<u64> {
+    status
+        .full_extra
+        .as_ref()
+        .and_then(|x| x["server_guid"].as_u64())
+}
+
+async fn broadcast_socket(ipv6: bool) -> Result<UdpSocket> {
+    let socket = if ipv6 {
+        UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).await?
+    } else {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(true)?;
+        socket
+    };
+    Ok(socket)
+}
+
+async fn collect_pongs(
+    socket: UdpSocket,
+    target: SocketAddr,
+    window: Duration,
+    found: &mut Vec<Discovered>,
+) -> Result<()> {
+    let timestamp = now_timestamp();
+    let start = Instant::now();
+    for id in [UNCONNECTED_PING, UNCONNECTED_PING_OPEN_CONNECTIONS] {
+        socket.send_to(&ping_packet(id, timestamp), target).await?;
+    }
+    log::debug!("Unconnected Pings sent to {}", target);
+
+    let deadline = start + window;
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    while let Ok(recv) = timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        // Errors such as ICMP unreachable concern a single datagram, keep listening
+        let (len, addr) = match recv {
+            Ok(recv) => recv,
+            Err(e) => {
+                log::debug!("Receive from {} failed: {}", target, e);
+                continue;
+            }
+        };
+        match parse_pong(&buf[..len], timestamp, start.elapsed(), Timings::default()) {
+            // Both ping variants are answered, keep one entry per server
+            Ok(status) => {
+                if !found
+                    .iter()
+                    .any(|d| d.addr == addr && server_guid(&d.status) == server_guid(&status))
+                {
+                    found.push(Discovered { addr, status });
+                }
+            }
+            Err(e) => log::debug!("Ignored datagram from {}: {}", addr, e),
+        }
+    }
+    Ok(())
+}
+
+/// Broadcast on IPv4 and multicast on IPv6 at the same time, collecting every pong in the window
+pub async fn discover_bedrock(window: Duration, interface: u32) -> Result<Vec<Discovered>> {
+    let mut found_v4 = vec![];
+    let mut found_v6 = vec![];
+    let v4 = async {
+        let socket = broadcast_socket(false).await?;
+        let target = SocketAddr::from((Ipv4Addr::BROADCAST, BEDROCK_PORT));
+        collect_pongs(socket, target, window, &mut found_v4).await
+    };
+    let v6 = async {
+        let socket = broadcast_socket(true).await?;
+        // Link-local multicast goes out of the interface given by the scope
+        let target = SocketAddr::V6(SocketAddrV6::new(
+            BEDROCK_MULTICAST_V6,
+            BEDROCK_PORT,
+            0,
+            interface,
+        ));
+        collect_pongs(socket, target, window, &mut found_v6).await
+    };
+    let (v4, v6) = tokio::join!(v4, v6);
+    match (v4, v6) {
+        (Err(e), Err(_)) => return Err(e),
+        (Err(e), _) => log::warn!("IPv4 broadcast failed: {}", e),
+        (_, Err(e)) => log::debug!("IPv6 multicast failed: {}", e),
+        _ => {}
+    }
+    found_v4.extend(found_v6);
+    Ok(found_v4)
+}
+
+fn multicast_listener(interface: u32) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // Clients on this host listen on the same port
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, JAVA_LAN_PORT)).into())?;
+    socket.join_multicast_v4_n(&JAVA_LAN_GROUP, &InterfaceIndexOrAddress::Index(interface))?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Collect "Open to LAN" announcements in the window, one entry per source and port
+pub async fn discover_java(window: Duration, interface: u32) -> Result<Vec<LanWorld>> {
+    let socket = multicast_listener(interface)?;
+    log::debug!("Listening on {}:{}", JAVA_LAN_GROUP, JAVA_LAN_PORT);
+
+    let deadline = Instant::now() + window;
+    let mut found: Vec<LanWorld> = vec![];
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    while let Ok(recv) = timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, source) = match recv {
+            Ok(recv) => recv,
+            Err(e) => {
+                log::debug!("Receive on {} failed: {}", JAVA_LAN_GROUP, e);
+                continue;
+            }
+        };
+        match decode_lan_announcement(&buf[..len]) {
+            Ok((motd, port)) => {
+                let addr = SocketAddr::new(source.ip(), port);
+                log::trace!("LAN announcement from {}: {}", source, motd);
+                if !found.iter().any(|w| w.addr == addr) {
+                    found.push(LanWorld { addr, motd });
+                }
+            }
+            Err(e) => log::debug!("Ignored datagram from {}: {}", source, e),
+        }
+    }
+    Ok(found)
+}
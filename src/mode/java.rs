@@ -1,17 +1,24 @@
 use crate::analyze::{MotdInfo, PlayerInfo, StatusPayload};
+use crate::logger;
 use crate::mode::QueryMode::JAVA;
 use crate::mode::QueryModeHandler;
 use crate::network::connection::connect_tcp;
+use crate::network::error::QueryError::InvalidResponse;
+use crate::network::error::{Failures, QueryError};
 use crate::network::resolve::{resolve_server_srv, sanitize_addr};
-use crate::network::schema::{read_string, read_var_int_stream, write_var_int};
-use crate::network::util::{io_timeout, now_timestamp};
-use anyhow::{Result, anyhow};
+use crate::network::schema::codec::{
+    MAX_PACKET_SIZE, decode_pong_response, decode_status_response,
+};
+use crate::network::schema::protocol::parse_protocol;
+use crate::network::schema::{read_packet_stream, write_var_int};
+use crate::network::util::{Timings, clock_skew, generic_timeout, now_timestamp, read_timeout};
+use anyhow::Result;
 use async_trait::async_trait;
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::BufMut;
 use clap::Args;
-use serde_json::{Value, from_str};
-use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use serde_json::{Value, from_str, json};
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 use tokio::task::JoinSet;
 
@@ -20,9 +27,8 @@ async fn single_ip_check(
     port: u16,
     stream: &mut TcpStream,
     protocol: i32,
+    mut timings: Timings,
 ) -> Result<StatusPayload> {
-    let time = Duration::from_secs(5);
-
     let mut handshake = vec![0];
     write_var_int(&mut handshake, protocol); // protocol_version
     write_var_int(&mut handshake, addr.len() as i32); // host string
@@ -32,47 +38,59 @@ async fn single_ip_check(
     let mut handshake_packed: Vec<u8> = vec![];
     write_var_int(&mut handshake_packed, handshake.len() as i32);
     handshake_packed.extend_from_slice(handshake.as_slice());
+    let status_start = Instant::now();
     stream.write_all(&handshake_packed).await?;
-    stream.write(&[1, 0]).await?; // status
+    stream.write_all(&[1, 0]).await?; // status
     stream.flush().await?;
     log::trace!("Handshake sent");
 
-    let handshake_recv_len = read_var_int_stream(stream).await?;
-    let mut handshake_recv = vec![0; handshake_recv_len as usize];
-    io_timeout(time, stream.read_exact(&mut handshake_recv), "Handshake").await?;
-    let mut recv_buf = BytesMut::from(handshake_recv.as_slice());
+    let handshake_recv = generic_timeout(
+        read_timeout(),
+        read_packet_stream(stream, MAX_PACKET_SIZE),
+        "Handshake",
+    )
+    .await?;
+    timings.status = Some(status_start.elapsed());
+    timings.status_size = Some(handshake_recv.len());
     log::trace!(
         "Handshake received from {}, length: {}",
         addr,
         handshake_recv.len()
     );
 
-    if recv_buf.remaining() == 0 || recv_buf.get_u8() != 0 {
-        return Err(anyhow!("Invalid received packet id"));
-    }
-    let json_str = read_string(&mut recv_buf)?;
-    if recv_buf.remaining() != 0 {
-        return Err(anyhow!(
-            "Packet length is invalid: Trailing {} bytes",
-            recv_buf.remaining()
-        ));
-    }
+    let json_str = decode_status_response(&handshake_recv).map_err(QueryError::from)?;
     log::trace!("Got json: {}", json_str);
     let mut decoded: Value = from_str(&json_str)?;
 
-    stream.write(&[9, 1]).await?; // ping_request
-    stream.write_i64(now_timestamp()).await?;
+    let sent_clock = now_timestamp();
+    let mut ping_request = vec![9, 1]; // ping_request
+    ping_request.put_i64(sent_clock);
+    let ping_start = Instant::now();
+    stream.write_all(&ping_request).await?;
     stream.flush().await?;
     log::trace!("Ping request sent");
 
-    let recv_pong = &mut [0; 10];
-    io_timeout(time, stream.read_exact(recv_pong), "Ping receiving").await?;
-    if recv_pong[0] != 9 || recv_pong[1] != 1 {
-        return Err(anyhow!("Invalid pong packet"));
-    }
-    let server_clock = i64::from_be_bytes(recv_pong[2..10].try_into().expect("Recv failed"));
-    let diff = now_timestamp() - server_clock;
-    log::trace!("Got ping time: {}", diff);
+    let recv_pong = generic_timeout(
+        read_timeout(),
+        read_packet_stream(stream, 16),
+        "Ping receiving",
+    )
+    .await?;
+    let round_trip = ping_start.elapsed();
+    timings.round_trip = Some(round_trip);
+    let echoed = decode_pong_response(&recv_pong).map_err(QueryError::from)?;
+    let ping = round_trip.as_millis() as i64;
+    let clock_skew = if echoed == sent_clock {
+        None
+    } else {
+        log::debug!("Pong payload {} does not match sent {}", echoed, sent_clock);
+        let skew = clock_skew(sent_clock, round_trip, echoed);
+        if skew.is_none() {
+            return Err(InvalidResponse("Invalid pong payload".to_string()).into());
+        }
+        skew
+    };
+    log::trace!("Got ping time: {}", ping);
 
     let players = decoded["players"].take();
     let player_count = players["online"].as_i64();
@@ -101,7 +119,8 @@ async fn single_ip_check(
 
     Ok(StatusPayload {
         mode: JAVA,
-        ping: diff,
+        ping,
+        clock_skew,
         max_players,
         player_count,
         players,
@@ -110,43 +129,40 @@ async fn single_ip_check(
         version_name,
         favicon,
         full_extra: Some(decoded),
+        timings,
     })
 }
 
-async fn safe_ip_check(
-    addr: String,
-    port: u16,
-    mut stream: TcpStream,
-    protocol: i32,
-) -> Result<StatusPayload> {
-    match single_ip_check(&addr, port, &mut stream, protocol).await {
-        Ok(resp) => Ok(resp),
-        Err(e) => Err(anyhow!("Protocol error in <{}:{}>: {}", addr, port, e)),
-    }
-}
-
 async fn check_java_server(
     addr: &str,
     port: u16,
-    streams: Vec<TcpStream>,
+    streams: Vec<(String, TcpStream, Timings)>,
     protocol: i32,
 ) -> Result<StatusPayload> {
     let mut set = JoinSet::new();
 
-    for stream in streams {
-        set.spawn(safe_ip_check(addr.to_string(), port, stream, protocol));
+    for (target, mut stream, timings) in streams {
+        let addr = addr.to_string();
+        set.spawn(logger::inherit(async move {
+            let res = single_ip_check(&addr, port, &mut stream, protocol, timings).await;
+            (target, res)
+        }));
     }
 
+    let mut failures = Failures::default();
     while let Some(join_res) = set.join_next().await {
-        if let Ok(res) = join_res {
+        if let Ok((target, res)) = join_res {
             match res {
                 Ok(res) => return Ok(res),
-                Err(e) => log::warn!("{}", e),
+                Err(e) => {
+                    log::debug!("Attempt to <{}> failed: {}", target, e);
+                    failures.record(target, e);
+                }
             }
         }
     }
 
-    Err(anyhow!("No server found"))
+    Err(failures.into_error("No server found"))
 }
 
 #[derive(Args, Debug)]
@@ -154,9 +170,40 @@ pub struct JavaModeArgs {
     /// Do not follow SRV redirection for Java query modes
     #[arg(long)]
     pub no_srv: bool,
-    /// Simulate the protocol version of the client
-    #[arg(long, default_value = "770")]
+    /// Simulate the protocol version of the client, as a number, a release name like `1.20.4`
+    /// or a snapshot revision like `snapshot-256`
+    #[arg(long, value_parser = parse_protocol, default_value = "770")]
     pub protocol: i32,
+    /// Repeat the status query with unusual protocol versions to see how the server reacts
+    #[arg(long)]
+    pub probe_handshake: bool,
+}
+
+/// Query a resolved target once, simulating a client with the given protocol version
+pub async fn query_target(host: &str, port: u16, protocol: i32) -> Result<StatusPayload> {
+    let streams = connect_tcp(host, port).await?;
+    check_java_server(host, port, streams, protocol).await
+}
+
+/// Protocol versions no real client sends: an ancient one and an invalid one
+const PROBE_PROTOCOLS: [i32; 2] = [47, -1];
+
+async fn probe_handshake(host: &str, port: u16) -> Value {
+    let mut results = vec![];
+    for protocol in PROBE_PROTOCOLS {
+        match query_target(host, port, protocol).await {
+            Ok(status) => results.push(json!({
+                "sent": protocol,
+                "protocol": status.protocol,
+                "version_name": status.version_name,
+            })),
+            Err(e) => {
+                log::debug!("Handshake probe with protocol {} failed: {}", protocol, e);
+                results.push(json!({"sent": protocol, "error": e.to_string()}));
+            }
+        }
+    }
+    Value::Array(results)
 }
 
 pub struct JavaQuery<'a> {
@@ -167,28 +214,42 @@ pub struct JavaQuery<'a> {
 impl QueryModeHandler for JavaQuery<'_> {
     async fn do_query(&self, addr: &str) -> Result<StatusPayload> {
         let mut addrs = vec![addr.to_string()];
+        let srv_start = Instant::now();
         if !self.args.no_srv {
             addrs.splice(0..0, resolve_server_srv(addr).await);
         }
+        let srv_time = srv_start.elapsed();
+        let mut failures = Failures::default();
         for addr in addrs {
             let (host, port) = sanitize_addr(&addr, 25565)?;
-            match connect_tcp(&host, port, Duration::new(5, 0)).await {
-                Ok(streams) => {
-                    if streams.is_empty() {
-                        log::warn!("No successful connection found for <{}:{}>", addr, port);
-                        continue;
-                    }
+            match connect_tcp(&host, port).await {
+                Ok(mut streams) => {
+                    streams
+                        .iter_mut()
+                        .for_each(|(_, _, timings)| timings.add_dns(srv_time));
                     match check_java_server(&host, port, streams, self.args.protocol).await {
-                        Ok(status) => return Ok(status),
-                        Err(e) => log::warn!("Failed to check <{}:{}>: {}", host, port, e),
+                        Ok(mut status) => {
+                            if self.args.probe_handshake
+                                && let Some(extra) = status.full_extra.as_mut()
+                                && extra.is_object()
+                            {
+                                extra["handshake_probe"] = probe_handshake(&host, port).await;
+                            }
+                            return Ok(status);
+                        }
+                        Err(e) => {
+                            log::debug!("Failed to check <{}:{}>: {}", host, port, e);
+                            failures.record(format!("{}:{}", host, port), e);
+                        }
                     }
                 }
                 Err(e) => {
-                    log::warn!("Failed to connect to <{}:{}>: {}", addr, port, e);
+                    log::debug!("Failed to connect to <{}:{}>: {}", host, port, e);
+                    failures.record(format!("{}:{}", host, port), e);
                 }
             }
         }
-        Err(anyhow!("No server found"))
+        Err(failures.into_error("No server found"))
     }
 }
 
@@ -1,15 +1,20 @@
 use crate::analyze::{MotdInfo, StatusPayload};
+use crate::logger;
 use crate::mode::QueryMode::LEGACY;
 use crate::mode::QueryModeHandler;
 use crate::mode::java::JavaModeArgs;
 use crate::network::connection::connect_tcp;
+use crate::network::error::QueryError::InvalidResponse;
+use crate::network::error::{Failures, QueryError};
 use crate::network::resolve::{resolve_server_srv, sanitize_addr};
-use crate::network::util::{io_timeout, now_timestamp};
-use anyhow::{Result, anyhow};
+use crate::network::schema::codec::decode_legacy_string;
+use crate::network::util::{Timings, io_timeout, read_timeout};
+use anyhow::Result;
 use async_trait::async_trait;
 use bytes::BufMut;
+use clap::{Args, ValueEnum};
 use serde_json::json;
-use std::time::Duration;
+use std::time::Instant;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::task::JoinSet;
@@ -19,156 +24,240 @@ const LEGACY_HEADER: [u8; 27] = [
     0x6E, 0x00, 0x67, 0x00, 0x48, 0x00, 0x6F, 0x00, 0x73, 0x00, 0x74,
 ];
 
-async fn single_ip_check(addr: &str, port: u16, stream: &mut TcpStream) -> Result<StatusPayload> {
-    let time = Duration::from_secs(5);
-
-    let utf16 = addr.encode_utf16().collect::<Vec<_>>();
-    let mut buf = Vec::from(LEGACY_HEADER);
-    let packet_len = (utf16.len() * 2 + 7) as u16;
-    buf.put_u16(packet_len);
-    buf.push(73);
-    buf.put_u16(utf16.len() as u16);
-    for short in utf16 {
-        buf.put_u16(short);
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum LegacyDialect {
+    /// Bare `FE`, Beta 1.8 to 1.3
+    Beta,
+    /// `FE 01`, 1.4 to 1.5
+    #[value(name = "1.4")]
+    V1_4,
+    /// `FE 01 FA MC|PingHost`, 1.6
+    #[value(name = "1.6")]
+    V1_6,
+    /// Try 1.6, 1.4 and Beta in order
+    Auto,
+}
+
+impl LegacyDialect {
+    fn name(&self) -> &'static str {
+        match self {
+            LegacyDialect::Beta => "beta",
+            LegacyDialect::V1_4 => "1.4",
+            LegacyDialect::V1_6 => "1.6",
+            LegacyDialect::Auto => "auto",
+        }
+    }
+
+    fn candidates(&self) -> Vec<LegacyDialect> {
+        match self {
+            LegacyDialect::Auto => vec![
+                LegacyDialect::V1_6,
+                LegacyDialect::V1_4,
+                LegacyDialect::Beta,
+            ],
+            dialect => vec![*dialect],
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct LegacyModeArgs {
+    /// Ping packet dialect used by legacy query mode
+    #[arg(long, default_value = "1.6")]
+    pub legacy_dialect: LegacyDialect,
+}
+
+fn build_request(dialect: LegacyDialect, addr: &str, port: u16) -> Vec<u8> {
+    match dialect {
+        LegacyDialect::Beta => vec![0xFE],
+        LegacyDialect::V1_4 => vec![0xFE, 0x01],
+        _ => {
+            let utf16 = addr.encode_utf16().collect::<Vec<_>>();
+            let mut buf = Vec::from(LEGACY_HEADER);
+            let packet_len = (utf16.len() * 2 + 7) as u16;
+            buf.put_u16(packet_len);
+            buf.push(73);
+            buf.put_u16(utf16.len() as u16);
+            for short in utf16 {
+                buf.put_u16(short);
+            }
+            buf.put_u16(0);
+            buf.put_u16(port);
+            buf
+        }
     }
-    buf.put_u16(0);
-    buf.put_u16(port);
+}
 
-    let send_time = now_timestamp();
+async fn single_ip_check(
+    addr: &str,
+    port: u16,
+    stream: &mut TcpStream,
+    dialect: LegacyDialect,
+    mut timings: Timings,
+) -> Result<StatusPayload> {
+    let buf = build_request(dialect, addr, port);
+    let status_start = Instant::now();
     stream.write_all(&buf).await?;
-    log::trace!("Legacy query sent, packet length = {}", packet_len);
+    log::trace!(
+        "Legacy query sent, dialect = {}, packet length = {}",
+        dialect.name(),
+        buf.len()
+    );
 
     let mut recv_buffer = [0u8; 3];
-    io_timeout(time, stream.read_exact(&mut recv_buffer), "Handshake").await?;
-    let ping = now_timestamp() - send_time;
+    io_timeout(
+        read_timeout(),
+        stream.read_exact(&mut recv_buffer),
+        "Handshake",
+    )
+    .await?;
+    let ping = status_start.elapsed().as_millis() as i64;
     log::trace!("Legacy query received, ping = {}", ping);
     if recv_buffer[0] != 0xFF {
-        return Err(anyhow!("Legacy header should be 0xFF"));
+        return Err(InvalidResponse("Legacy header should be 0xFF".to_string()).into());
     }
 
-    let recv_len = u16::from_be_bytes([recv_buffer[1], recv_buffer[2]]) * 2;
-    let mut recv = vec![0; recv_len as usize];
-    stream.read_exact(&mut recv).await?;
-    let u16buf = recv
-        .chunks_exact(2)
-        .into_iter()
-        .map(|a| u16::from_be_bytes([a[0], a[1]]))
-        .collect::<Vec<_>>();
-    let str = String::from_utf16(&u16buf)?;
+    let recv_len = u16::from_be_bytes([recv_buffer[1], recv_buffer[2]]) as usize * 2;
+    let mut recv = vec![0; recv_len];
+    io_timeout(read_timeout(), stream.read_exact(&mut recv), "Handshake").await?;
+    timings.status = Some(status_start.elapsed());
+    timings.status_size = Some(recv.len() + 3);
+    let str = decode_legacy_string(&recv).map_err(QueryError::from)?;
     log::trace!("Legacy query received from {}: {}", addr, str);
 
-    if str.starts_with("\u{00A7}1\0") {
+    if let Some(body) = str.strip_prefix("\u{00A7}1\0") {
         log::debug!("Legacy query version 1");
-        let parts = str.split('\0').collect::<Vec<_>>();
-        if parts.len() < 6 {
-            return Err(anyhow!("Legacy query string is invalid"));
-        }
-        if parts.len() > 6 {
-            log::warn!("Legacy query string has too many parts");
-            log::warn!("Data will be collected, but can not ensure data is correct");
+        // protocol, version, motd, online, max
+        let parts = body.split('\0').collect::<Vec<_>>();
+        if parts.len() != 5 {
+            return Err(InvalidResponse(format!(
+                "Legacy query string is invalid: Expected 5 fields, found {}",
+                parts.len()
+            ))
+            .into());
         }
         Ok(StatusPayload {
             mode: LEGACY,
             ping,
-            max_players: Some(parts[5].parse()?),
-            player_count: Some(parts[4].parse()?),
+            clock_skew: None,
+            max_players: Some(parts[4].parse()?),
+            player_count: Some(parts[3].parse()?),
             players: None,
-            motd: Some(MotdInfo::String(parts[3].to_string())),
-            protocol: Some(parts[1].parse()?),
-            version_name: Some(parts[2].to_string()),
+            motd: Some(MotdInfo::String(parts[2].to_string())),
+            protocol: Some(parts[0].parse()?),
+            version_name: Some(parts[1].to_string()),
             favicon: None,
-            full_extra: Some(json!({"legacy_version": 1})),
+            full_extra: Some(json!({"legacy_version": 1, "legacy_dialect": dialect.name()})),
+            timings,
         })
     } else {
         log::debug!("Legacy query version 0");
-        let parts = str.split('\u{00A7}').collect::<Vec<_>>();
-        if parts.len() < 3 {
-            return Err(anyhow!("Legacy query string is invalid"));
-        }
-        if parts.len() > 3 {
-            log::warn!("Legacy query string has too many parts");
-            log::warn!("Data will be collected, but can not ensure data is correct");
+        // motd, online, max; only the last two separators are meaningful
+        let parts = str.rsplitn(3, '\u{00A7}').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(InvalidResponse("Legacy query string is invalid".to_string()).into());
         }
         Ok(StatusPayload {
             mode: LEGACY,
             ping,
-            max_players: Some(parts[2].parse()?),
+            clock_skew: None,
+            max_players: Some(parts[0].parse()?),
             player_count: Some(parts[1].parse()?),
             players: None,
-            motd: Some(MotdInfo::String(parts[0].to_string())),
+            motd: Some(MotdInfo::String(parts[2].to_string())),
             protocol: None,
             version_name: None,
             favicon: None,
-            full_extra: Some(json!({"legacy_version": 0})),
+            full_extra: Some(json!({"legacy_version": 0, "legacy_dialect": dialect.name()})),
+            timings,
         })
     }
 }
 
-async fn safe_ip_check(addr: String, port: u16, mut stream: TcpStream) -> Result<StatusPayload> {
-    match single_ip_check(&addr, port, &mut stream).await {
-        Ok(resp) => Ok(resp),
-        Err(e) => Err(anyhow!("Protocol error in <{}:{}>: {}", addr, port, e)),
-    }
-}
-
 async fn check_legacy_server(
     addr: &str,
     port: u16,
-    streams: Vec<TcpStream>,
+    streams: Vec<(String, TcpStream, Timings)>,
+    dialect: LegacyDialect,
 ) -> Result<StatusPayload> {
     let mut set = JoinSet::new();
 
-    for stream in streams {
-        set.spawn(safe_ip_check(addr.to_string(), port, stream));
+    for (target, mut stream, timings) in streams {
+        let addr = addr.to_string();
+        set.spawn(logger::inherit(async move {
+            let res = single_ip_check(&addr, port, &mut stream, dialect, timings).await;
+            (target, res)
+        }));
     }
 
+    let mut failures = Failures::default();
     while let Some(join_res) = set.join_next().await {
-        if let Ok(res) = join_res {
+        if let Ok((target, res)) = join_res {
             match res {
                 Ok(res) => return Ok(res),
-                Err(e) => log::warn!("{}", e),
+                Err(e) => {
+                    log::debug!("Attempt to <{}> failed: {}", target, e);
+                    failures.record(target, e);
+                }
             }
         }
     }
 
-    Err(anyhow!("No server found"))
+    Err(failures.into_error("No server found"))
 }
 
 pub struct LegacyQuery<'a> {
     args: &'a JavaModeArgs,
+    dialect: LegacyDialect,
 }
 
 #[async_trait]
 impl QueryModeHandler for LegacyQuery<'_> {
     async fn do_query(&self, addr: &str) -> Result<StatusPayload> {
         let mut addrs = vec![addr.to_string()];
+        let srv_start = Instant::now();
         if !self.args.no_srv {
             addrs.splice(0..0, resolve_server_srv(addr).await);
         }
+        let srv_time = srv_start.elapsed();
+        let mut failures = Failures::default();
         for addr in addrs {
             let (host, port) = sanitize_addr(&addr, 25565)?;
-            match connect_tcp(&host, port, Duration::new(5, 0)).await {
-                Ok(streams) => {
-                    if streams.is_empty() {
-                        log::warn!("No successful connection found for <{}:{}>", addr, port);
-                        continue;
+            // Servers close the connection after the kick, so every dialect needs a new one
+            for dialect in self.dialect.candidates() {
+                match connect_tcp(&host, port).await {
+                    Ok(mut streams) => {
+                        streams
+                            .iter_mut()
+                            .for_each(|(_, _, timings)| timings.add_dns(srv_time));
+                        match check_legacy_server(&host, port, streams, dialect).await {
+                            Ok(status) => return Ok(status),
+                            Err(e) => {
+                                log::debug!(
+                                    "Failed to check <{}:{}> with dialect {}: {}",
+                                    host,
+                                    port,
+                                    dialect.name(),
+                                    e
+                                );
+                                failures.record(format!("{}:{}", host, port), e);
+                            }
+                        }
                     }
-                    match check_legacy_server(&host, port, streams).await {
-                        Ok(status) => return Ok(status),
-                        Err(e) => log::warn!("Failed to check <{}:{}>: {}", addr, port, e),
+                    Err(e) => {
+                        log::debug!("Failed to connect to <{}:{}>: {}", host, port, e);
+                        failures.record(format!("{}:{}", host, port), e);
+                        break;
                     }
                 }
-                Err(e) => {
-                    log::warn!("Failed to connect to <{}:{}>: {}", addr, port, e);
-                }
             }
         }
-        Err(anyhow!("No server found"))
+        Err(failures.into_error("No server found"))
     }
 }
 
 impl LegacyQuery<'_> {
-    pub fn new(args: &'_ JavaModeArgs) -> LegacyQuery<'_> {
-        LegacyQuery { args }
+    pub fn new(args: &'_ JavaModeArgs, dialect: LegacyDialect) -> LegacyQuery<'_> {
+        LegacyQuery { args, dialect }
     }
 }
@@ -1,18 +1,25 @@
 use crate::analyze::StatusPayload;
 use crate::mode::QueryMode::*;
-use crate::mode::bedrock::BedrockQuery;
+use crate::mode::auto::AutoQuery;
+use crate::mode::bedrock::{BedrockModeArgs, BedrockQuery};
 use crate::mode::java::{JavaModeArgs, JavaQuery};
 #[cfg(feature = "ping-legacy")]
-use crate::mode::legacy::LegacyQuery;
-use anyhow::{Result, anyhow};
+use crate::mode::legacy::{LegacyModeArgs, LegacyQuery};
+use crate::mode::sweep::{SweepModeArgs, SweepQuery};
+use crate::network::error::QueryError::InvalidArgument;
+use anyhow::Result;
 use async_trait::async_trait;
 use clap::{Args, ValueEnum};
 use std::collections::HashMap;
 
+pub mod auto;
 pub mod bedrock;
+pub mod discover;
 pub mod java;
 #[cfg(feature = "ping-legacy")]
 pub mod legacy;
+pub mod servers_dat;
+pub mod sweep;
 
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, ValueEnum)]
 pub enum QueryMode {
@@ -20,6 +27,22 @@ pub enum QueryMode {
     BEDROCK,
     #[cfg(feature = "ping-legacy")]
     LEGACY,
+    /// Probe Java (falling back to legacy) and Bedrock at the same time
+    AUTO,
+    /// Repeat the Java status query for every protocol in `--sweep-protocols`
+    SWEEP,
+}
+
+impl QueryMode {
+    /// Modes answering with a Java Edition status
+    pub fn is_java_family(&self) -> bool {
+        match self {
+            JAVA => true,
+            #[cfg(feature = "ping-legacy")]
+            LEGACY => true,
+            _ => false,
+        }
+    }
 }
 
 #[async_trait]
@@ -36,7 +59,7 @@ impl QueryEngine<'_> {
         if let Some(handler) = self.modes.get(&mode) {
             handler.do_query(addr).await
         } else {
-            Err(anyhow!("No available query mode"))
+            Err(InvalidArgument("No available query mode".to_string()).into())
         }
     }
 }
@@ -45,13 +68,35 @@ impl QueryEngine<'_> {
 pub struct ModeArgs {
     #[command(flatten)]
     java: JavaModeArgs,
+    #[command(flatten)]
+    bedrock: BedrockModeArgs,
+    #[cfg(feature = "ping-legacy")]
+    #[command(flatten)]
+    legacy: LegacyModeArgs,
+    #[command(flatten)]
+    sweep: SweepModeArgs,
+}
+
+impl ModeArgs {
+    pub fn client_protocol(&self) -> i32 {
+        self.java.protocol
+    }
+
+    pub fn follow_ports(&self) -> bool {
+        self.bedrock.follow_ports
+    }
 }
 
 pub fn init_query_engine(args: &'_ ModeArgs) -> QueryEngine<'_> {
     let mut modes: HashMap<QueryMode, Box<dyn QueryModeHandler>> = HashMap::new();
     modes.insert(JAVA, Box::new(JavaQuery::new(&args.java)));
-    modes.insert(BEDROCK, Box::new(BedrockQuery::new()));
+    modes.insert(BEDROCK, Box::new(BedrockQuery::new(&args.bedrock)));
     #[cfg(feature = "ping-legacy")]
-    modes.insert(LEGACY, Box::new(LegacyQuery::new(&args.java)));
+    modes.insert(
+        LEGACY,
+        Box::new(LegacyQuery::new(&args.java, args.legacy.legacy_dialect)),
+    );
+    modes.insert(AUTO, Box::new(AutoQuery::new(&args.java, &args.bedrock)));
+    modes.insert(SWEEP, Box::new(SweepQuery::new(&args.java, &args.sweep)));
     QueryEngine { modes }
 }
@@ -0,0 +1,100 @@
+use crate::network::schema::nbt::{MAX_STRING_LENGTH, Tag, decode_nbt, encode_nbt};
+use anyhow::{Result, anyhow};
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+/// Icons are stored as bare base64 PNG, favicons come as data URLs
+const FAVICON_PREFIX: &str = "data:image/png;base64,";
+
+#[derive(Args, Debug)]
+pub struct ServersDatArgs {
+    /// Client `servers.dat` to ping, or a plain address list with `--from-list`
+    pub input: PathBuf,
+    /// Read the input as one address per line instead of a `servers.dat`
+    #[arg(long)]
+    pub from_list: bool,
+    /// Write the entries with refreshed icons to this `servers.dat`
+    #[arg(long)]
+    pub export: Option<PathBuf>,
+}
+
+/// One entry of the multiplayer list, keeping every field the client wrote
+pub struct ServerEntry {
+    tag: Tag,
+}
+
+impl ServerEntry {
+    fn from_address(address: &str) -> ServerEntry {
+        ServerEntry {
+            tag: Tag::Compound(vec![
+                ("name".to_string(), Tag::String(address.to_string())),
+                ("ip".to_string(), Tag::String(address.to_string())),
+            ]),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.tag.get("name").and_then(Tag::as_str).unwrap_or("")
+    }
+
+    pub fn ip(&self) -> &str {
+        self.tag.get("ip").and_then(Tag::as_str).unwrap_or("")
+    }
+
+    /// Replace the icon with a fetched favicon, false if it is not a base64 PNG or too long to store
+    pub fn set_icon(&mut self, favicon: &str) -> bool {
+        match favicon.strip_prefix(FAVICON_PREFIX) {
+            // Base64 is ASCII, so its length is already the encoded length
+            Some(icon) if icon.len() <= MAX_STRING_LENGTH => {
+                self.tag.set("icon", Tag::String(icon.to_string()));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_servers_dat(data: &[u8]) -> Result<Vec<ServerEntry>> {
+    let (_, root) = decode_nbt(data)?;
+    let servers = root
+        .get("servers")
+        .and_then(Tag::as_list)
+        .ok_or(anyhow!("No servers list"))?;
+    let mut entries = vec![];
+    for (index, tag) in servers.iter().enumerate() {
+        if tag.get("ip").and_then(Tag::as_str).is_some() {
+            entries.push(ServerEntry { tag: tag.clone() });
+        } else {
+            log::warn!(
+                "Skipped server list entry #{} without an address",
+                index + 1
+            );
+        }
+    }
+    Ok(entries)
+}
+
+/// Addresses one per line, blank lines and `#` comments are skipped
+fn parse_address_list(text: &str) -> Vec<ServerEntry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(ServerEntry::from_address)
+        .collect()
+}
+
+pub async fn read_entries(path: &Path, from_list: bool) -> Result<Vec<ServerEntry>> {
+    let data = tokio::fs::read(path).await?;
+    if from_list {
+        Ok(parse_address_list(&String::from_utf8(data)?))
+    } else {
+        parse_servers_dat(&data)
+    }
+}
+
+pub async fn write_entries(path: &Path, entries: &[ServerEntry]) -> Result<()> {
+    let servers = entries.iter().map(|entry| entry.tag.clone()).collect();
+    let root = Tag::Compound(vec![("servers".to_string(), Tag::List(servers))]);
+    tokio::fs::write(path, encode_nbt("", &root)?).await?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use crate::analyze::{MotdInfo, StatusPayload};
+use crate::mode::QueryModeHandler;
+use crate::mode::java::{JavaModeArgs, query_target};
+use crate::network::error::Failures;
+use crate::network::resolve::{resolve_server_srv, sanitize_addr};
+use crate::network::schema::protocol::{JAVA_PROTOCOLS, parse_protocol};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use clap::Args;
+use serde_json::{Value, json};
+
+#[derive(Args, Debug)]
+pub struct SweepModeArgs {
+    /// Protocols tried by sweep query mode, as numbers, release names or `first..last` ranges of known releases
+    /// (defaults to one release per major line, `4..774` tries every known release)
+    #[arg(long, value_parser = parse_protocol_span, value_delimiter = ',', default_values = ["1.8", "1.12.2", "1.16.5", "1.20.1", "1.21.11"])]
+    pub sweep_protocols: Vec<(i32, i32)>,
+}
+
+fn parse_protocol_span(value: &str) -> Result<(i32, i32)> {
+    match value.split_once("..") {
+        Some((first, last)) => {
+            let span = (parse_protocol(first)?, parse_protocol(last)?);
+            if span.0 > span.1 {
+                return Err(anyhow!(
+                    "Range {} is reversed, write the older release first",
+                    value
+                ));
+            }
+            Ok(span)
+        }
+        None => {
+            let protocol = parse_protocol(value)?;
+            Ok((protocol, protocol))
+        }
+    }
+}
+
+fn expand_protocols(spans: &[(i32, i32)]) -> Vec<i32> {
+    let mut protocols = vec![];
+    for &(first, last) in spans {
+        if first == last {
+            protocols.push(first);
+        } else {
+            protocols.extend(
+                JAVA_PROTOCOLS
+                    .iter()
+                    .map(|e| e.protocol)
+                    .filter(|p| (first..=last).contains(p)),
+            );
+        }
+    }
+    // Spans may overlap, every protocol is queried once and in order
+    protocols.sort_unstable();
+    protocols.dedup();
+    protocols
+}
+
+fn sweep_entry(sent: i32, status: &StatusPayload) -> Value {
+    let motd = match status.motd.as_ref() {
+        Some(MotdInfo::String(motd)) => json!(motd),
+        Some(MotdInfo::Component(motd)) => motd.clone(),
+        None => Value::Null,
+    };
+    json!({
+        "sent": sent,
+        "protocol": status.protocol,
+        "version_name": status.version_name,
+        "motd": motd,
+    })
+}
+
+pub struct SweepQuery<'a> {
+    java: &'a JavaModeArgs,
+    args: &'a SweepModeArgs,
+}
+
+#[async_trait]
+impl QueryModeHandler for SweepQuery<'_> {
+    async fn do_query(&self, addr: &str) -> Result<StatusPayload> {
+        let mut addrs = vec![addr.to_string()];
+        if !self.java.no_srv {
+            addrs.splice(0..0, resolve_server_srv(addr).await);
+        }
+
+        // Settle on one target with the selected protocol, then sweep only that target
+        let mut found = None;
+        let mut failures = Failures::default();
+        for addr in addrs {
+            let (host, port) = sanitize_addr(&addr, 25565)?;
+            match query_target(&host, port, self.java.protocol).await {
+                Ok(status) => {
+                    found = Some((host, port, status));
+                    break;
+                }
+                Err(e) => {
+                    log::debug!("Attempt to <{}:{}> failed: {}", host, port, e);
+                    failures.record(format!("{}:{}", host, port), e);
+                }
+            }
+        }
+        let Some((host, port, mut status)) = found else {
+            return Err(failures.into_error("No server found"));
+        };
+
+        let protocols = expand_protocols(&self.args.sweep_protocols);
+        let mut entries = vec![];
+        for protocol in protocols {
+            if protocol == self.java.protocol {
+                entries.push(sweep_entry(protocol, &status));
+                continue;
+            }
+            log::debug!("Sweeping <{}:{}> with protocol {}", host, port, protocol);
+            match query_target(&host, port, protocol).await {
+                Ok(swept) => entries.push(sweep_entry(protocol, &swept)),
+                Err(e) => {
+                    log::debug!("Sweep with protocol {} failed: {}", protocol, e);
+                    entries.push(json!({"sent": protocol, "error": e.to_string()}));
+                }
+            }
+        }
+
+        let extra = status.full_extra.get_or_insert_with(|| json!({}));
+        if let Value::Object(map) = extra {
+            map.insert("sweep".to_string(), Value::Array(entries));
+        }
+        Ok(status)
+    }
+}
+
+impl SweepQuery<'_> {
+    pub fn new<'a>(java: &'a JavaModeArgs, args: &'a SweepModeArgs) -> SweepQuery<'a> {
+        SweepQuery { java, args }
+    }
+}
@@ -0,0 +1,214 @@
+//! Typed errors of the network and mode layers, exit codes are derived from their failure class.
+
+use crate::network::schema::codec::DecodeError;
+use anyhow::Error;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+
+/// Ordered by how far a query got, the furthest one wins when several attempts fail.
+/// Local failures come last, they are not about the server and would go unnoticed otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// The address or an option cannot be used as given
+    InvalidArgument,
+    /// The host name did not resolve to any address
+    Resolve,
+    /// Nothing accepted the connection
+    Unreachable,
+    Timeout,
+    /// The server answered something that is not a valid status
+    Protocol,
+    /// The proxy was unusable, so every query through it fails the same way
+    Proxy,
+    /// A local file or the terminal could not be read or written
+    LocalIo,
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FailureKind::InvalidArgument => "invalid argument",
+            FailureKind::Resolve => "dns",
+            FailureKind::Unreachable => "unreachable",
+            FailureKind::Timeout => "timeout",
+            FailureKind::Protocol => "protocol",
+            FailureKind::Proxy => "proxy",
+            FailureKind::LocalIo => "local io",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One address tried while querying, with why it failed
+#[derive(Debug)]
+pub struct Attempt {
+    pub target: String,
+    pub error: Error,
+}
+
+impl Attempt {
+    /// The innermost cause, the target already tells where it happened
+    pub fn cause(&self) -> &(dyn std::error::Error + 'static) {
+        self.error.root_cause()
+    }
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    InvalidArgument(String),
+    Dns {
+        host: String,
+    },
+    Connect {
+        addr: SocketAddr,
+        source: std::io::Error,
+    },
+    Timeout {
+        phase: String,
+    },
+    Proxy(String),
+    /// The answer could not be decoded
+    Protocol {
+        kind: DecodeError,
+    },
+    /// The answer was decoded but its content is wrong
+    InvalidResponse(String),
+    AllAttemptsFailed {
+        what: &'static str,
+        attempts: Vec<Attempt>,
+    },
+}
+
+impl QueryError {
+    pub fn kind(&self) -> FailureKind {
+        match self {
+            QueryError::InvalidArgument(_) => FailureKind::InvalidArgument,
+            QueryError::Dns { .. } => FailureKind::Resolve,
+            QueryError::Connect { source, .. } => io_failure_kind(source),
+            QueryError::Timeout { .. } => FailureKind::Timeout,
+            QueryError::Proxy(_) => FailureKind::Proxy,
+            QueryError::Protocol { .. } | QueryError::InvalidResponse(_) => FailureKind::Protocol,
+            // Without any attempt there was nothing to try, so the name did not resolve
+            QueryError::AllAttemptsFailed { attempts, .. } => attempts
+                .iter()
+                .map(|attempt| failure_kind(&attempt.error))
+                .max()
+                .unwrap_or(FailureKind::Resolve),
+        }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::InvalidArgument(message) => write!(f, "{}", message),
+            QueryError::Dns { host } => write!(f, "Cannot resolve {}", host),
+            QueryError::Connect { addr, source } => {
+                write!(f, "Cannot connect to {}: {}", addr, source)
+            }
+            QueryError::Timeout { phase } => write!(f, "{} timed out", phase),
+            QueryError::Proxy(message) => write!(f, "{}", message),
+            QueryError::Protocol { kind } => write!(f, "{}", kind),
+            QueryError::InvalidResponse(message) => write!(f, "{}", message),
+            QueryError::AllAttemptsFailed { what, attempts } => match attempts.len() {
+                1 => write!(
+                    f,
+                    "{}: <{}>: {}",
+                    what,
+                    attempts[0].target,
+                    attempts[0].cause()
+                ),
+                n => write!(f, "{}, {} attempts failed", what, n),
+            },
+        }
+    }
+}
+
+impl std::error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QueryError::Connect { source, .. } => Some(source),
+            QueryError::Protocol { kind } => Some(kind),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for QueryError {
+    fn from(kind: DecodeError) -> Self {
+        QueryError::Protocol { kind }
+    }
+}
+
+fn io_failure_kind(e: &std::io::Error) -> FailureKind {
+    match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => FailureKind::Timeout,
+        ErrorKind::ConnectionRefused
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted
+        | ErrorKind::PermissionDenied
+        | ErrorKind::HostUnreachable
+        | ErrorKind::NetworkUnreachable
+        | ErrorKind::NetworkDown
+        | ErrorKind::AddrNotAvailable
+        | ErrorKind::NotConnected => FailureKind::Unreachable,
+        // Anything else, such as an early EOF or invalid data, happened while talking to the
+        // server after the connection worked
+        _ => FailureKind::Protocol,
+    }
+}
+
+/// Failure class of reading or writing a local file: a missing input or a file that does not
+/// parse is the argument's fault, other I/O errors are local ones
+pub fn local_failure_kind(e: &Error) -> FailureKind {
+    match e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+    {
+        Some(io) if io.kind() != ErrorKind::NotFound => FailureKind::LocalIo,
+        _ => FailureKind::InvalidArgument,
+    }
+}
+
+/// Failure class of an error, errors from outside this module are classified by their type
+pub fn failure_kind(e: &Error) -> FailureKind {
+    for cause in e.chain() {
+        if let Some(query) = cause.downcast_ref::<QueryError>() {
+            return query.kind();
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return io_failure_kind(io);
+        }
+    }
+    // Decoding, JSON and number parsing errors all mean the answer was wrong
+    FailureKind::Protocol
+}
+
+/// Attempts of a query that has not succeeded yet, nested aggregates are flattened
+#[derive(Debug, Default)]
+pub struct Failures(Vec<Attempt>);
+
+impl Failures {
+    pub fn record(&mut self, target: impl ToString, error: Error) {
+        match error.downcast::<QueryError>() {
+            Ok(QueryError::AllAttemptsFailed { attempts, .. }) => self.0.extend(attempts),
+            Ok(error) => self.0.push(Attempt {
+                target: target.to_string(),
+                error: error.into(),
+            }),
+            Err(error) => self.0.push(Attempt {
+                target: target.to_string(),
+                error,
+            }),
+        }
+    }
+
+    pub fn into_error(self, what: &'static str) -> Error {
+        QueryError::AllAttemptsFailed {
+            what,
+            attempts: self.0,
+        }
+        .into()
+    }
+}
@@ -1,4 +1,6 @@
+pub mod connection;
+pub mod error;
+pub mod proxy_protocol;
 pub mod resolve;
 pub mod schema;
 pub mod util;
-pub mod connection;
\ No newline at end of file
@@ -0,0 +1,135 @@
+use bytes::BufMut;
+use clap::{Args, ValueEnum};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+
+#[derive(Args, Debug)]
+pub struct ProxyProtocolSettings {
+    /// Send a HAProxy PROXY protocol header before queries
+    /// [Note: Only v2 can be sent over UDP, through a proxy only IP destinations are announced]
+    #[arg(long)]
+    pub proxy_protocol: Option<ProxyProtocolVersion>,
+    /// Source address announced in the PROXY protocol header instead of the real one
+    #[arg(long, requires = "proxy_protocol")]
+    pub proxy_protocol_source: Option<SocketAddr>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ProxyProtocolVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+
+static PROXY_PROTOCOL: OnceLock<(ProxyProtocolVersion, Option<SocketAddr>)> = OnceLock::new();
+
+pub fn setup_proxy_protocol(settings: &ProxyProtocolSettings) {
+    if let Some(version) = settings.proxy_protocol {
+        PROXY_PROTOCOL
+            .set((version, settings.proxy_protocol_source))
+            .expect("Should set proxy protocol");
+    }
+}
+
+fn to_ipv6(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port()),
+        IpAddr::V6(_) => addr,
+    }
+}
+
+fn unify_family(source: SocketAddr, dest: SocketAddr) -> (SocketAddr, SocketAddr) {
+    if source.is_ipv4() == dest.is_ipv4() {
+        (source, dest)
+    } else {
+        (to_ipv6(source), to_ipv6(dest))
+    }
+}
+
+fn encode_v1(source: Option<SocketAddr>, dest: Option<SocketAddr>) -> Vec<u8> {
+    let line = match (source, dest) {
+        (Some(source), Some(dest)) => {
+            let (source, dest) = unify_family(source, dest);
+            let family = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+            format!(
+                "PROXY {} {} {} {} {}\r\n",
+                family,
+                source.ip(),
+                dest.ip(),
+                source.port(),
+                dest.port()
+            )
+        }
+        _ => "PROXY UNKNOWN\r\n".to_string(),
+    };
+    line.into_bytes()
+}
+
+fn encode_v2(
+    transport: Transport,
+    source: Option<SocketAddr>,
+    dest: Option<SocketAddr>,
+) -> Vec<u8> {
+    let mut header = Vec::from(V2_SIGNATURE);
+    let (source, dest) = match (source, dest) {
+        (Some(source), Some(dest)) => unify_family(source, dest),
+        _ => {
+            // LOCAL command, the receiver uses the real connection endpoints
+            header.push(0x20);
+            header.push(0x00);
+            header.put_u16(0);
+            return header;
+        }
+    };
+    header.push(0x21);
+    let protocol = match transport {
+        Transport::Tcp => 0x01,
+        Transport::Udp => 0x02,
+    };
+    match (source.ip(), dest.ip()) {
+        (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+            header.push(0x10 | protocol);
+            header.put_u16(12);
+            header.extend_from_slice(&src_ip.octets());
+            header.extend_from_slice(&dst_ip.octets());
+        }
+        (IpAddr::V6(src_ip), IpAddr::V6(dst_ip)) => {
+            header.push(0x20 | protocol);
+            header.put_u16(36);
+            header.extend_from_slice(&src_ip.octets());
+            header.extend_from_slice(&dst_ip.octets());
+        }
+        _ => unreachable!("Address families should be unified"),
+    }
+    header.put_u16(source.port());
+    header.put_u16(dest.port());
+    header
+}
+
+/// Build the PROXY protocol header to prepend for a connection, if enabled.
+/// `local` is the real local address, replaced by the spoofed source if configured.
+pub fn proxy_protocol_header(
+    transport: Transport,
+    local: Option<SocketAddr>,
+    dest: Option<SocketAddr>,
+) -> Option<Vec<u8>> {
+    let (version, spoofed) = PROXY_PROTOCOL.get()?;
+    let source = spoofed.or(local);
+    match (version, transport) {
+        (ProxyProtocolVersion::V1, Transport::Tcp) => Some(encode_v1(source, dest)),
+        (ProxyProtocolVersion::V1, Transport::Udp) => {
+            log::debug!("PROXY protocol v1 does not support UDP, header not sent");
+            None
+        }
+        (ProxyProtocolVersion::V2, _) => Some(encode_v2(transport, source, dest)),
+    }
+}
@@ -1,10 +1,11 @@
+use crate::network::error::QueryError::InvalidArgument;
 use anyhow::Result;
 use hickory_resolver::Resolver;
 use regex_lite::Regex;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::LazyLock;
 
-const ADDRESS_REGEX: LazyLock<Regex> =
+static ADDRESS_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^(.+):(\d+)$").expect("Compile regex failed!"));
 
 pub async fn resolve_server_srv(addr: &str) -> Vec<String> {
@@ -42,7 +43,12 @@ pub async fn resolve_server_srv(addr: &str) -> Vec<String> {
 
 pub fn sanitize_addr(addr: &str, default_port: u16) -> Result<(String, u16)> {
     match ADDRESS_REGEX.captures(addr) {
-        Some(captures) => Ok((captures[1].to_string(), captures[2].parse()?)),
+        Some(captures) => Ok((
+            captures[1].to_string(),
+            captures[2]
+                .parse()
+                .map_err(|e| InvalidArgument(format!("Invalid port in {}: {}", addr, e)))?,
+        )),
         None => Ok((addr.to_string(), default_port)),
     }
 }
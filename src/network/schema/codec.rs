@@ -0,0 +1,537 @@
+//! Panic-free decoders for everything received from servers.
+//!
+//! This module only depends on `bytes` and `std` so fuzz targets can include it directly.
+
+use bytes::{Buf, BytesMut};
+use std::fmt::{Display, Formatter};
+
+/// Largest packet a vanilla server can frame (3-byte varint length)
+pub const MAX_PACKET_SIZE: usize = 2097151;
+/// Largest status JSON accepted, vanilla limits it to 32767 UTF-16 units. The favicon
+/// is part of the JSON, so this bounds it as well.
+pub const MAX_JSON_SIZE: usize = 32767 * 4;
+/// Largest UDP payload a Bedrock pong can have
+pub const MAX_DATAGRAM_SIZE: usize = 65507;
+
+pub const MAGIC_HIGH: u64 = 0x00ffff00fefefefeu64;
+pub const MAGIC_LOW: u64 = 0xfdfdfdfd12345678u64;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeError {
+    UnexpectedEof {
+        needed: usize,
+        remaining: usize,
+    },
+    VarIntTooLong,
+    NegativeLength(i64),
+    TooLarge {
+        what: &'static str,
+        size: usize,
+        max: usize,
+    },
+    InvalidPacketId {
+        expected: i32,
+        found: i32,
+    },
+    TrailingBytes(usize),
+    InvalidMagic,
+    InvalidUtf8,
+    #[cfg_attr(not(feature = "ping-legacy"), allow(dead_code))]
+    InvalidUtf16,
+    MissingField(&'static str),
+    InvalidTag(u8),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof { needed, remaining } => write!(
+                f,
+                "Unexpected end of data: need {} bytes, {} remaining",
+                needed, remaining
+            ),
+            DecodeError::VarIntTooLong => write!(f, "Invalid varint: Too long"),
+            DecodeError::NegativeLength(len) => write!(f, "Negative length {}", len),
+            DecodeError::TooLarge { what, size, max } => {
+                write!(f, "{} too large: {} bytes (max {})", what, size, max)
+            }
+            DecodeError::InvalidPacketId { expected, found } => write!(
+                f,
+                "Invalid received packet id: expected {:#04x}, found {:#04x}",
+                expected, found
+            ),
+            DecodeError::TrailingBytes(len) => {
+                write!(f, "Packet length is invalid: Trailing {} bytes", len)
+            }
+            DecodeError::InvalidMagic => write!(f, "Invalid magic number"),
+            DecodeError::InvalidUtf8 => write!(f, "Invalid UTF-8 string"),
+            DecodeError::InvalidUtf16 => write!(f, "Invalid UTF-16 string"),
+            DecodeError::MissingField(what) => write!(f, "Missing or invalid field: {}", what),
+            DecodeError::InvalidTag(id) => write!(f, "Invalid NBT tag type {:#04x}", id),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+pub type DecodeResult<T> = Result<T, DecodeError>;
+
+pub fn check_size(what: &'static str, size: usize, max: usize) -> DecodeResult<()> {
+    if size > max {
+        Err(DecodeError::TooLarge { what, size, max })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn check_length(len: i64, what: &'static str, max: usize) -> DecodeResult<usize> {
+    if len < 0 {
+        return Err(DecodeError::NegativeLength(len));
+    }
+    let len = len as usize;
+    check_size(what, len, max)?;
+    Ok(len)
+}
+
+pub fn ensure(buf: &impl Buf, needed: usize) -> DecodeResult<()> {
+    if buf.remaining() < needed {
+        Err(DecodeError::UnexpectedEof {
+            needed,
+            remaining: buf.remaining(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn get_u8(buf: &mut impl Buf) -> DecodeResult<u8> {
+    ensure(buf, 1)?;
+    Ok(buf.get_u8())
+}
+
+pub fn get_u16(buf: &mut impl Buf) -> DecodeResult<u16> {
+    ensure(buf, 2)?;
+    Ok(buf.get_u16())
+}
+
+pub fn get_i64(buf: &mut impl Buf) -> DecodeResult<i64> {
+    ensure(buf, 8)?;
+    Ok(buf.get_i64())
+}
+
+pub fn get_u64(buf: &mut impl Buf) -> DecodeResult<u64> {
+    ensure(buf, 8)?;
+    Ok(buf.get_u64())
+}
+
+/// Accumulates varint bytes one at a time, so stream and buffer readers share the checks
+#[derive(Default)]
+pub struct VarIntDecoder {
+    result: i32,
+    offset: u32,
+}
+
+impl VarIntDecoder {
+    /// Returns the value once the last byte has been pushed
+    pub fn push(&mut self, byte: u8) -> DecodeResult<Option<i32>> {
+        if self.offset >= 5 {
+            return Err(DecodeError::VarIntTooLong);
+        }
+        self.result |= i32::from(byte & 0x7F) << (self.offset * 7);
+        self.offset += 1;
+        if byte & 0x80 == 0 {
+            Ok(Some(self.result))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub fn read_var_int_buf(buf: &mut impl Buf) -> DecodeResult<i32> {
+    let mut decoder = VarIntDecoder::default();
+    loop {
+        if let Some(value) = decoder.push(get_u8(buf)?)? {
+            return Ok(value);
+        }
+    }
+}
+
+pub fn read_string(buf: &mut BytesMut, max: usize) -> DecodeResult<String> {
+    let length = check_length(read_var_int_buf(buf)? as i64, "String", max)?;
+    ensure(buf, length)?;
+    String::from_utf8(buf.split_to(length).to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+/// Decode a Java status response packet (without its length prefix) into the JSON string
+pub fn decode_status_response(packet: &[u8]) -> DecodeResult<String> {
+    check_size("Packet", packet.len(), MAX_PACKET_SIZE)?;
+    let mut buf = BytesMut::from(packet);
+    let id = read_var_int_buf(&mut buf)?;
+    if id != 0 {
+        return Err(DecodeError::InvalidPacketId {
+            expected: 0,
+            found: id,
+        });
+    }
+    let json = read_string(&mut buf, MAX_JSON_SIZE)?;
+    if buf.remaining() != 0 {
+        return Err(DecodeError::TrailingBytes(buf.remaining()));
+    }
+    Ok(json)
+}
+
+/// Decode a Java pong response packet (without its length prefix) into the echoed payload
+pub fn decode_pong_response(packet: &[u8]) -> DecodeResult<i64> {
+    let mut buf = packet;
+    let id = read_var_int_buf(&mut buf)?;
+    if id != 1 {
+        return Err(DecodeError::InvalidPacketId {
+            expected: 1,
+            found: id,
+        });
+    }
+    let payload = get_i64(&mut buf)?;
+    if buf.remaining() != 0 {
+        return Err(DecodeError::TrailingBytes(buf.remaining()));
+    }
+    Ok(payload)
+}
+
+/// Decode the UTF-16 body of a legacy kick packet, `data` starts after the 0xFF header
+/// and the length prefix
+#[cfg_attr(not(feature = "ping-legacy"), allow(dead_code))]
+pub fn decode_legacy_string(data: &[u8]) -> DecodeResult<String> {
+    if !data.len().is_multiple_of(2) {
+        return Err(DecodeError::UnexpectedEof {
+            needed: data.len() + 1,
+            remaining: data.len(),
+        });
+    }
+    let u16buf = data
+        .chunks_exact(2)
+        .map(|a| u16::from_be_bytes([a[0], a[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16(&u16buf).map_err(|_| DecodeError::InvalidUtf16)
+}
+
+#[derive(Debug)]
+pub struct UnconnectedPong {
+    pub time: i64,
+    pub server_guid: u64,
+    pub server_id: String,
+}
+
+/// Decode a Bedrock (RakNet) Unconnected Pong datagram
+pub fn decode_unconnected_pong(data: &[u8]) -> DecodeResult<UnconnectedPong> {
+    check_size("Datagram", data.len(), MAX_DATAGRAM_SIZE)?;
+    let mut buf = data;
+    let id = get_u8(&mut buf)?;
+    if id != 0x1C {
+        return Err(DecodeError::InvalidPacketId {
+            expected: 0x1C,
+            found: id as i32,
+        });
+    }
+    let time = get_i64(&mut buf)?;
+    let server_guid = get_u64(&mut buf)?;
+    let magic_high = get_u64(&mut buf)?;
+    let magic_low = get_u64(&mut buf)?;
+    if magic_high != MAGIC_HIGH || magic_low != MAGIC_LOW {
+        return Err(DecodeError::InvalidMagic);
+    }
+    let str_len = get_u16(&mut buf)? as usize;
+    ensure(&buf, str_len)?;
+    if buf.remaining() != str_len {
+        return Err(DecodeError::TrailingBytes(buf.remaining() - str_len));
+    }
+    let server_id = String::from_utf8(buf.to_vec()).map_err(|_| DecodeError::InvalidUtf8)?;
+    Ok(UnconnectedPong {
+        time,
+        server_guid,
+        server_id,
+    })
+}
+
+const GAME_MODES: [&str; 4] = ["Survival", "Creative", "Adventure", "Spectator"];
+
+/// Fields of the `;`-separated server ID string in a Bedrock pong
+#[derive(Debug)]
+pub struct BedrockStatus {
+    pub edition: String,
+    pub motd: String,
+    pub protocol: i64,
+    pub version: String,
+    pub player_count: i64,
+    pub max_players: i64,
+    pub server_unique_id: Option<String>,
+    pub level_name: Option<String>,
+    pub game_mode: Option<String>,
+    /// Numeric id of the game mode, 0 for survival, 1 for creative and 2 for adventure
+    pub game_mode_id: Option<i64>,
+    pub port_v4: Option<u16>,
+    pub port_v6: Option<u16>,
+    /// Fields after the IPv6 port, sent by newer versions
+    pub extra_fields: Vec<String>,
+    pub field_count: usize,
+    pub trailing_separator: bool,
+}
+
+/// Decode the server ID string of a Bedrock pong. Server names may contain `;`, so the
+/// numeric block and the game mode anchor the fields around them.
+pub fn decode_bedrock_status(server_id: &str) -> DecodeResult<BedrockStatus> {
+    let trailing_separator = server_id.ends_with(';');
+    let mut parts = server_id.split(';').collect::<Vec<_>>();
+    if trailing_separator {
+        parts.pop();
+    }
+    let field_count = parts.len();
+
+    // protocol, version, online and max players follow the MOTD
+    let numeric = |i: usize| parts.get(i).and_then(|p| p.parse::<i64>().ok());
+    let start = (2..parts.len())
+        .find(|&i| {
+            numeric(i).is_some()
+                && parts.get(i + 1).is_some()
+                && numeric(i + 2).is_some()
+                && numeric(i + 3).is_some()
+        })
+        .ok_or(DecodeError::MissingField("protocol and player counts"))?;
+    let edition = parts[0].to_string();
+    let motd = parts[1..start].join(";");
+    let protocol = numeric(start).ok_or(DecodeError::MissingField("protocol"))?;
+    let version = parts[start + 1].to_string();
+    let player_count = numeric(start + 2).ok_or(DecodeError::MissingField("player count"))?;
+    let max_players = numeric(start + 3).ok_or(DecodeError::MissingField("max players"))?;
+    let server_unique_id = parts.get(start + 4).map(|p| p.to_string());
+
+    let rest = parts.get(start + 5..).unwrap_or_default();
+    let (level_name, game_mode, tail) = match (1..rest.len()).find(|&i| {
+        GAME_MODES
+            .iter()
+            .any(|mode| rest[i].eq_ignore_ascii_case(mode))
+    }) {
+        Some(i) => (Some(rest[..i].join(";")), Some(rest[i]), &rest[i + 1..]),
+        None => (
+            rest.first().map(|p| p.to_string()),
+            rest.get(1).copied(),
+            rest.get(2..).unwrap_or_default(),
+        ),
+    };
+
+    Ok(BedrockStatus {
+        edition,
+        motd,
+        protocol,
+        version,
+        player_count,
+        max_players,
+        server_unique_id,
+        level_name,
+        game_mode: game_mode.map(|p| p.to_string()),
+        game_mode_id: tail.first().and_then(|p| p.parse().ok()),
+        port_v4: tail.get(1).and_then(|p| p.parse().ok()),
+        port_v6: tail.get(2).and_then(|p| p.parse().ok()),
+        extra_fields: tail.iter().skip(3).map(|p| p.to_string()).collect(),
+        field_count,
+        trailing_separator,
+    })
+}
+
+/// Decode a Java "Open to LAN" announcement, `[MOTD]text[/MOTD][AD]port[/AD]`, into
+/// the MOTD and the announced port
+pub fn decode_lan_announcement(data: &[u8]) -> DecodeResult<(String, u16)> {
+    let text = std::str::from_utf8(data).map_err(|_| DecodeError::InvalidUtf8)?;
+    let between = |open: &str, close: &str| {
+        let start = text.find(open)? + open.len();
+        let end = start + text[start..].find(close)?;
+        Some(&text[start..end])
+    };
+    let motd = between("[MOTD]", "[/MOTD]").ok_or(DecodeError::MissingField("MOTD"))?;
+    let ad = between("[AD]", "[/AD]").ok_or(DecodeError::MissingField("AD"))?;
+    // Old clients announced `host:port`
+    let port = ad
+        .rsplit(':')
+        .next()
+        .and_then(|p| p.trim().parse().ok())
+        .ok_or(DecodeError::MissingField("port"))?;
+    Ok((motd.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+
+    fn pong(server_id: &str) -> Vec<u8> {
+        let mut data = vec![0x1C];
+        data.put_i64(1234);
+        data.put_u64(0x0102030405060708);
+        data.put_u64(MAGIC_HIGH);
+        data.put_u64(MAGIC_LOW);
+        data.put_u16(server_id.len() as u16);
+        data.extend(server_id.as_bytes());
+        data
+    }
+
+    #[test]
+    fn var_int_vectors() {
+        let cases: [(i32, &[u8]); 5] = [
+            (0, &[0x00]),
+            (127, &[0x7F]),
+            (300, &[0xAC, 0x02]),
+            (i32::MAX, &[0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+            (-1, &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+        ];
+        for (value, bytes) in cases {
+            assert_eq!(read_var_int_buf(&mut &bytes[..]), Ok(value));
+        }
+        assert_eq!(
+            read_var_int_buf(&mut &[0xFF; 6][..]),
+            Err(DecodeError::VarIntTooLong)
+        );
+        assert!(matches!(
+            read_var_int_buf(&mut &[0x80][..]),
+            Err(DecodeError::UnexpectedEof { .. })
+        ));
+    }
+
+    #[test]
+    fn java_status_response() {
+        let json = r#"{"description":"A Minecraft Server"}"#;
+        let mut packet = vec![0x00, json.len() as u8];
+        packet.extend(json.as_bytes());
+        assert_eq!(decode_status_response(&packet).as_deref(), Ok(json));
+        packet.push(0);
+        assert_eq!(
+            decode_status_response(&packet),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            decode_status_response(&[0x01, 0x00]),
+            Err(DecodeError::InvalidPacketId {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            decode_pong_response(&[0x01, 0, 0, 0, 0, 0, 0, 0x01, 0x02]),
+            Ok(0x0102)
+        );
+    }
+
+    #[test]
+    fn legacy_string() {
+        assert_eq!(
+            decode_legacy_string(&[0x00, 0xA7, 0x00, 0x31]).as_deref(),
+            Ok("§1")
+        );
+        assert!(decode_legacy_string(&[0x00]).is_err());
+        assert_eq!(
+            decode_legacy_string(&[0xD8, 0x00]),
+            Err(DecodeError::InvalidUtf16)
+        );
+    }
+
+    #[test]
+    fn unconnected_pong() {
+        let server_id = "MCPE;Dedicated Server;390;1.14.60;0;10;13253860892328930865;";
+        let decoded = decode_unconnected_pong(&pong(server_id)).expect("Should decode");
+        assert_eq!(decoded.time, 1234);
+        assert_eq!(decoded.server_guid, 0x0102030405060708);
+        assert_eq!(decoded.server_id, server_id);
+
+        let mut bad_magic = pong(server_id);
+        bad_magic[20] ^= 0xFF;
+        assert_eq!(
+            decode_unconnected_pong(&bad_magic).unwrap_err(),
+            DecodeError::InvalidMagic
+        );
+        let mut trailing = pong(server_id);
+        trailing.push(0);
+        assert_eq!(
+            decode_unconnected_pong(&trailing).unwrap_err(),
+            DecodeError::TrailingBytes(1)
+        );
+        let truncated = pong(server_id);
+        assert!(decode_unconnected_pong(&truncated[..truncated.len() - 1]).is_err());
+        assert!(decode_unconnected_pong(&[0x1D]).is_err());
+    }
+
+    #[test]
+    fn bedrock_status_fields() {
+        let status = decode_bedrock_status(
+            "MCPE;Dedicated Server;390;1.14.60;0;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;0;1;",
+        )
+        .expect("Should decode");
+        assert_eq!(status.edition, "MCPE");
+        assert_eq!(status.motd, "Dedicated Server");
+        assert_eq!(status.protocol, 390);
+        assert_eq!(status.version, "1.14.60");
+        assert_eq!(status.player_count, 0);
+        assert_eq!(status.max_players, 10);
+        assert_eq!(
+            status.server_unique_id.as_deref(),
+            Some("13253860892328930865")
+        );
+        assert_eq!(status.level_name.as_deref(), Some("Bedrock level"));
+        assert_eq!(status.game_mode.as_deref(), Some("Survival"));
+        assert_eq!(status.game_mode_id, Some(1));
+        assert_eq!(status.port_v4, Some(19132));
+        assert_eq!(status.port_v6, Some(19133));
+        assert_eq!(status.extra_fields, ["0", "1"]);
+        assert_eq!(status.field_count, 14);
+        assert!(status.trailing_separator);
+    }
+
+    #[test]
+    fn bedrock_status_with_separators_in_names() {
+        let status = decode_bedrock_status(
+            "MCPE;Hub; now open;712;1.21.20;5;100;42;My;World;Creative;1;19132;19133",
+        )
+        .expect("Should decode");
+        assert_eq!(status.motd, "Hub; now open");
+        assert_eq!(status.protocol, 712);
+        assert_eq!(status.level_name.as_deref(), Some("My;World"));
+        assert_eq!(status.game_mode.as_deref(), Some("Creative"));
+        assert_eq!(status.port_v6, Some(19133));
+        assert!(!status.trailing_separator);
+    }
+
+    #[test]
+    fn short_bedrock_status() {
+        let status = decode_bedrock_status("MCPE;Old server;137;1.2;1;20").expect("Should decode");
+        assert_eq!(status.max_players, 20);
+        assert_eq!(status.server_unique_id, None);
+        assert_eq!(status.game_mode, None);
+        assert_eq!(status.port_v4, None);
+        assert_eq!(
+            decode_bedrock_status("MCPE;No numbers").unwrap_err(),
+            DecodeError::MissingField("protocol and player counts")
+        );
+    }
+
+    #[test]
+    fn lan_announcements() {
+        assert_eq!(
+            decode_lan_announcement(b"[MOTD]Steve - New World[/MOTD][AD]41234[/AD]"),
+            Ok(("Steve - New World".to_string(), 41234))
+        );
+        // Old clients announced the host as well
+        assert_eq!(
+            decode_lan_announcement(b"[MOTD]World[/MOTD][AD]192.168.1.2:25565[/AD]"),
+            Ok(("World".to_string(), 25565))
+        );
+        assert_eq!(
+            decode_lan_announcement(b"[MOTD]World[/MOTD]"),
+            Err(DecodeError::MissingField("AD"))
+        );
+        assert_eq!(
+            decode_lan_announcement(b"[MOTD]World[/MOTD][AD]70000[/AD]"),
+            Err(DecodeError::MissingField("port"))
+        );
+        assert_eq!(
+            decode_lan_announcement(&[0xFF]),
+            Err(DecodeError::InvalidUtf8)
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Decoder for the compressed mod list in `forgeData.d`.
+
+use super::codec::{
+    DecodeError, DecodeResult, MAX_JSON_SIZE, check_size, get_u8, get_u16, read_string,
+    read_var_int_buf,
+};
+use bytes::{Buf, BufMut, BytesMut};
+
+#[derive(Debug)]
+pub struct ForgeChannel {
+    pub path: String,
+    pub version: String,
+    pub required: bool,
+}
+
+#[derive(Debug)]
+pub struct ForgeMod {
+    pub name: String,
+    /// `None` if the mod is ignored on the server side
+    pub version: Option<String>,
+    pub channels: Vec<ForgeChannel>,
+}
+
+#[derive(Debug)]
+pub struct ForgeData {
+    pub truncated: bool,
+    pub mods: Vec<ForgeMod>,
+    pub non_mod_channels: Vec<ForgeChannel>,
+}
+
+fn read_forge_channel(buf: &mut BytesMut) -> DecodeResult<ForgeChannel> {
+    Ok(ForgeChannel {
+        path: read_string(buf, MAX_JSON_SIZE)?,
+        version: read_string(buf, MAX_JSON_SIZE)?,
+        required: get_u8(buf)? != 0,
+    })
+}
+
+/// Decode the compressed `forgeData.d` string sent by Forge servers
+pub fn decode_forge_data(data: &str) -> DecodeResult<ForgeData> {
+    check_size("ForgeData", data.len(), MAX_JSON_SIZE)?;
+    let chars = data.encode_utf16().collect::<Vec<u16>>();
+    if chars.len() < 2 {
+        return Err(DecodeError::UnexpectedEof {
+            needed: 2,
+            remaining: chars.len(),
+        });
+    }
+
+    let buffer_len = chars[0] as usize | (chars[1] as usize) << 15;
+    let mut buffer = BytesMut::with_capacity(buffer_len.min(MAX_JSON_SIZE));
+    let mut bits_in_buf = 0;
+    let mut buf = 0u32;
+    for char in chars[2..].iter() {
+        while bits_in_buf >= 8 {
+            buffer.put_u8((buf & 0xFF) as u8);
+            buf >>= 8;
+            bits_in_buf -= 8;
+        }
+        buf |= ((*char as u32) & 32767) << bits_in_buf;
+        bits_in_buf += 15;
+    }
+    while bits_in_buf > 0 {
+        buffer.put_u8((buf & 0xFF) as u8);
+        buf >>= 8;
+        bits_in_buf -= 8;
+    }
+
+    let truncated = get_u8(&mut buffer)? != 0;
+    let size = get_u16(&mut buffer)?;
+    let mut mods = Vec::new();
+    for _ in 0..size {
+        let flag = read_var_int_buf(&mut buffer)?;
+        let ch_size = flag >> 1 & (!(1 << 31));
+        let ignore_server_only = flag & 1 != 0;
+        let name = read_string(&mut buffer, MAX_JSON_SIZE)?;
+        let version = if ignore_server_only {
+            None
+        } else {
+            Some(read_string(&mut buffer, MAX_JSON_SIZE)?)
+        };
+        let mut channels = Vec::new();
+        for _ in 0..ch_size {
+            channels.push(read_forge_channel(&mut buffer)?);
+        }
+        mods.push(ForgeMod {
+            name,
+            version,
+            channels,
+        });
+    }
+
+    let mut non_mod_channels = Vec::new();
+    if buffer.has_remaining() {
+        let count = read_var_int_buf(&mut buffer)?;
+        for _ in 0..count {
+            non_mod_channels.push(read_forge_channel(&mut buffer)?);
+        }
+    }
+
+    Ok(ForgeData {
+        truncated,
+        mods,
+        non_mod_channels,
+    })
+}
@@ -0,0 +1,42 @@
+pub mod codec;
+#[cfg(feature = "analyze-forge-info")]
+pub mod forge;
+pub mod nbt;
+pub mod protocol;
+
+use crate::network::schema::codec::{VarIntDecoder, check_length};
+use anyhow::Result;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub fn write_var_int(vec: &mut Vec<u8>, num: i32) {
+    let mut value = num;
+    loop {
+        if value & 0xFFFFFF80u32 as i32 != 0 {
+            vec.push((value & 0x7F | 0x80) as u8);
+            value = value >> 7 & 0x1FFFFFF;
+        } else {
+            vec.push((value & 0x7F) as u8);
+            break;
+        }
+    }
+}
+
+pub async fn read_var_int_stream(stream: &mut (impl AsyncRead + Unpin)) -> Result<i32> {
+    let mut decoder = VarIntDecoder::default();
+    loop {
+        if let Some(value) = decoder.push(stream.read_u8().await?)? {
+            return Ok(value);
+        }
+    }
+}
+
+/// Read a length-prefixed packet, refusing lengths above `max` before allocating
+pub async fn read_packet_stream(
+    stream: &mut (impl AsyncRead + Unpin),
+    max: usize,
+) -> Result<Vec<u8>> {
+    let len = check_length(read_var_int_stream(stream).await? as i64, "Packet", max)?;
+    let mut packet = vec![0; len];
+    stream.read_exact(&mut packet).await?;
+    Ok(packet)
+}
@@ -0,0 +1,492 @@
+//! Reader and writer for uncompressed NBT, as used by the client's `servers.dat`.
+
+use super::codec::{DecodeError, DecodeResult, check_length, check_size, ensure, get_u8, get_u16};
+use bytes::{Buf, BufMut};
+
+/// Vanilla refuses deeper nesting, which also keeps recursion bounded
+const MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    /// Entries keep their file order so a rewritten file stays close to the original
+    Compound(Vec<(String, Tag)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => TAG_BYTE,
+            Tag::Short(_) => TAG_SHORT,
+            Tag::Int(_) => TAG_INT,
+            Tag::Long(_) => TAG_LONG,
+            Tag::Float(_) => TAG_FLOAT,
+            Tag::Double(_) => TAG_DOUBLE,
+            Tag::ByteArray(_) => TAG_BYTE_ARRAY,
+            Tag::String(_) => TAG_STRING,
+            Tag::List(_) => TAG_LIST,
+            Tag::Compound(_) => TAG_COMPOUND,
+            Tag::IntArray(_) => TAG_INT_ARRAY,
+            Tag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Replace the value under `key`, appending it if missing
+    pub fn set(&mut self, key: &str, value: Tag) {
+        if let Tag::Compound(entries) = self {
+            match entries.iter_mut().find(|(k, _)| k == key) {
+                Some(entry) => entry.1 = value,
+                None => entries.push((key.to_string(), value)),
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(list) => Some(list),
+            _ => None,
+        }
+    }
+}
+
+/// Decode Java's modified UTF-8, which encodes NUL and supplementary characters differently
+fn decode_mutf8(bytes: &[u8]) -> DecodeResult<String> {
+    if let Ok(s) = std::str::from_utf8(bytes)
+        && !s.contains('\0')
+    {
+        return Ok(s.to_string());
+    }
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().copied();
+    while let Some(b) = iter.next() {
+        let mut next = || match iter.next() {
+            Some(c) if c & 0xC0 == 0x80 => Ok(u16::from(c & 0x3F)),
+            _ => Err(DecodeError::InvalidUtf8),
+        };
+        let unit = match b {
+            0x01..=0x7F => u16::from(b),
+            0xC0..=0xDF => (u16::from(b & 0x1F) << 6) | next()?,
+            0xE0..=0xEF => (u16::from(b & 0x0F) << 12) | (next()? << 6) | next()?,
+            _ => return Err(DecodeError::InvalidUtf8),
+        };
+        units.push(unit);
+    }
+    String::from_utf16(&units).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+fn encode_mutf8(s: &str, out: &mut Vec<u8>) {
+    for unit in s.encode_utf16() {
+        match unit {
+            0x01..=0x7F => out.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                out.push(0xC0 | (unit >> 6) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                out.push(0xE0 | (unit >> 12) as u8);
+                out.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+}
+
+fn read_name(buf: &mut &[u8]) -> DecodeResult<String> {
+    let len = get_u16(buf)? as usize;
+    ensure(buf, len)?;
+    let s = decode_mutf8(&buf[..len])?;
+    buf.advance(len);
+    Ok(s)
+}
+
+/// Array length, checked against the remaining data before anything is allocated
+fn read_length(buf: &mut &[u8], element_size: usize) -> DecodeResult<usize> {
+    ensure(buf, 4)?;
+    let len = check_length(i64::from(buf.get_i32()), "NBT array", buf.remaining())?;
+    ensure(buf, len * element_size)?;
+    Ok(len)
+}
+
+fn read_payload(buf: &mut &[u8], id: u8, depth: usize) -> DecodeResult<Tag> {
+    if depth > MAX_DEPTH {
+        return Err(DecodeError::TooLarge {
+            what: "NBT depth",
+            size: depth,
+            max: MAX_DEPTH,
+        });
+    }
+    let tag = match id {
+        TAG_BYTE => Tag::Byte(get_u8(buf)? as i8),
+        TAG_SHORT => Tag::Short(get_u16(buf)? as i16),
+        TAG_INT => {
+            ensure(buf, 4)?;
+            Tag::Int(buf.get_i32())
+        }
+        TAG_LONG => {
+            ensure(buf, 8)?;
+            Tag::Long(buf.get_i64())
+        }
+        TAG_FLOAT => {
+            ensure(buf, 4)?;
+            Tag::Float(buf.get_f32())
+        }
+        TAG_DOUBLE => {
+            ensure(buf, 8)?;
+            Tag::Double(buf.get_f64())
+        }
+        TAG_BYTE_ARRAY => {
+            let len = read_length(buf, 1)?;
+            Tag::ByteArray((0..len).map(|_| buf.get_i8()).collect())
+        }
+        TAG_STRING => Tag::String(read_name(buf)?),
+        TAG_LIST => {
+            let element = get_u8(buf)?;
+            // Every element but End takes at least one byte
+            let len = read_length(buf, 1)?;
+            if element == TAG_END && len > 0 {
+                return Err(DecodeError::MissingField("NBT list type"));
+            }
+            let mut list = Vec::with_capacity(len);
+            for _ in 0..len {
+                list.push(read_payload(buf, element, depth + 1)?);
+            }
+            Tag::List(list)
+        }
+        TAG_COMPOUND => {
+            let mut entries = vec![];
+            loop {
+                let id = get_u8(buf)?;
+                if id == TAG_END {
+                    break;
+                }
+                let name = read_name(buf)?;
+                entries.push((name, read_payload(buf, id, depth + 1)?));
+            }
+            Tag::Compound(entries)
+        }
+        TAG_INT_ARRAY => {
+            let len = read_length(buf, 4)?;
+            Tag::IntArray((0..len).map(|_| buf.get_i32()).collect())
+        }
+        TAG_LONG_ARRAY => {
+            let len = read_length(buf, 8)?;
+            Tag::LongArray((0..len).map(|_| buf.get_i64()).collect())
+        }
+        found => return Err(DecodeError::InvalidTag(found)),
+    };
+    Ok(tag)
+}
+
+/// Decode an uncompressed NBT file into its root name and compound
+pub fn decode_nbt(data: &[u8]) -> DecodeResult<(String, Tag)> {
+    let mut buf = data;
+    let id = get_u8(&mut buf)?;
+    if id != TAG_COMPOUND {
+        return Err(DecodeError::InvalidTag(id));
+    }
+    let name = read_name(&mut buf)?;
+    let root = read_payload(&mut buf, id, 0)?;
+    if buf.remaining() != 0 {
+        return Err(DecodeError::TrailingBytes(buf.remaining()));
+    }
+    Ok((name, root))
+}
+
+/// Longest string an NBT length prefix can describe, in encoded bytes
+pub const MAX_STRING_LENGTH: usize = u16::MAX as usize;
+
+fn write_name(out: &mut Vec<u8>, name: &str) -> DecodeResult<()> {
+    let mut encoded = vec![];
+    encode_mutf8(name, &mut encoded);
+    // Cutting it would corrupt the string, vanilla refuses to write it as well
+    check_size("NBT string", encoded.len(), MAX_STRING_LENGTH)?;
+    out.put_u16(encoded.len() as u16);
+    out.extend(encoded);
+    Ok(())
+}
+
+fn write_payload(out: &mut Vec<u8>, tag: &Tag) -> DecodeResult<()> {
+    match tag {
+        Tag::Byte(v) => out.put_i8(*v),
+        Tag::Short(v) => out.put_i16(*v),
+        Tag::Int(v) => out.put_i32(*v),
+        Tag::Long(v) => out.put_i64(*v),
+        Tag::Float(v) => out.put_f32(*v),
+        Tag::Double(v) => out.put_f64(*v),
+        Tag::ByteArray(values) => {
+            out.put_i32(values.len() as i32);
+            values.iter().for_each(|v| out.put_i8(*v));
+        }
+        Tag::String(s) => write_name(out, s)?,
+        Tag::List(list) => {
+            out.put_u8(list.first().map_or(TAG_END, Tag::id));
+            out.put_i32(list.len() as i32);
+            for element in list {
+                write_payload(out, element)?;
+            }
+        }
+        Tag::Compound(entries) => {
+            for (name, value) in entries {
+                out.put_u8(value.id());
+                write_name(out, name)?;
+                write_payload(out, value)?;
+            }
+            out.put_u8(TAG_END);
+        }
+        Tag::IntArray(values) => {
+            out.put_i32(values.len() as i32);
+            values.iter().for_each(|v| out.put_i32(*v));
+        }
+        Tag::LongArray(values) => {
+            out.put_i32(values.len() as i32);
+            values.iter().for_each(|v| out.put_i64(*v));
+        }
+    }
+    Ok(())
+}
+
+/// Encode a root compound as an uncompressed NBT file
+pub fn encode_nbt(name: &str, root: &Tag) -> DecodeResult<Vec<u8>> {
+    let mut out = vec![root.id()];
+    write_name(&mut out, name)?;
+    write_payload(&mut out, root)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(root: &Tag) -> Tag {
+        let encoded = encode_nbt("root", root).expect("Should encode");
+        let (name, decoded) = decode_nbt(&encoded).expect("Should decode");
+        assert_eq!(name, "root");
+        decoded
+    }
+
+    /// Lists nested `levels` deep inside a root compound
+    fn nested(levels: usize) -> Tag {
+        let mut tag = Tag::List(vec![]);
+        for _ in 1..levels {
+            tag = Tag::List(vec![tag]);
+        }
+        Tag::Compound(vec![("nested".to_string(), tag)])
+    }
+
+    #[test]
+    fn every_tag_round_trips() {
+        let root = Tag::Compound(vec![
+            ("byte".to_string(), Tag::Byte(-1)),
+            ("short".to_string(), Tag::Short(i16::MIN)),
+            ("int".to_string(), Tag::Int(25565)),
+            ("long".to_string(), Tag::Long(i64::MAX)),
+            ("float".to_string(), Tag::Float(0.5)),
+            ("double".to_string(), Tag::Double(-2.25)),
+            ("bytes".to_string(), Tag::ByteArray(vec![1, -2, 3])),
+            (
+                "string".to_string(),
+                Tag::String("Server \0 é 😀".to_string()),
+            ),
+            (
+                "list".to_string(),
+                Tag::List(vec![
+                    Tag::String("a".to_string()),
+                    Tag::String("b".to_string()),
+                ]),
+            ),
+            ("empty".to_string(), Tag::List(vec![])),
+            (
+                "ints".to_string(),
+                Tag::IntArray(vec![i32::MIN, 0, i32::MAX]),
+            ),
+            ("longs".to_string(), Tag::LongArray(vec![-1, 1])),
+            (
+                "compound".to_string(),
+                Tag::Compound(vec![(
+                    "ip".to_string(),
+                    Tag::String("localhost".to_string()),
+                )]),
+            ),
+        ]);
+        assert_eq!(round_trip(&root), root);
+    }
+
+    #[test]
+    fn known_file_decodes() {
+        // TAG_Compound "" { TAG_String "name": "Hi" }
+        let data = [
+            0x0A, 0x00, 0x00, 0x08, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x00, 0x02, b'H', b'i',
+            0x00,
+        ];
+        let (name, root) = decode_nbt(&data).expect("Should decode");
+        assert_eq!(name, "");
+        assert_eq!(root.get("name").and_then(Tag::as_str), Some("Hi"));
+        assert_eq!(encode_nbt("", &root), Ok(data.to_vec()));
+    }
+
+    #[test]
+    fn modified_utf8_vectors() {
+        let cases: [(&str, &[u8]); 4] = [
+            ("A", &[0x41]),
+            ("\0", &[0xC0, 0x80]),
+            ("é", &[0xC3, 0xA9]),
+            // Supplementary characters are written as two encoded surrogates
+            ("😀", &[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]),
+        ];
+        for (text, bytes) in cases {
+            let mut encoded = vec![];
+            encode_mutf8(text, &mut encoded);
+            assert_eq!(encoded, bytes, "encoding {:?}", text);
+            assert_eq!(
+                decode_mutf8(bytes).as_deref(),
+                Ok(text),
+                "decoding {:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_modified_utf8_is_rejected() {
+        for bytes in [&[0x00][..], &[0x80], &[0xC3], &[0xE0, 0x80], &[0xFF]] {
+            assert_eq!(
+                decode_mutf8(bytes),
+                Err(DecodeError::InvalidUtf8),
+                "{:?}",
+                bytes
+            );
+        }
+        // A lone surrogate cannot become a Rust string
+        assert_eq!(
+            decode_mutf8(&[0xED, 0xA0, 0xBD]),
+            Err(DecodeError::InvalidUtf8)
+        );
+    }
+
+    #[test]
+    fn standard_utf8_is_accepted() {
+        // Some third-party editors write plain UTF-8, which reads the same when it has no NUL
+        assert_eq!(decode_mutf8(&[0xF0, 0x9F, 0x98, 0x80]).as_deref(), Ok("😀"));
+    }
+
+    #[test]
+    fn longest_string_is_written() {
+        let root = Tag::Compound(vec![(
+            "icon".to_string(),
+            Tag::String("a".repeat(MAX_STRING_LENGTH)),
+        )]);
+        assert_eq!(round_trip(&root), root);
+    }
+
+    #[test]
+    fn oversized_string_is_refused() {
+        let too_large = Err(DecodeError::TooLarge {
+            what: "NBT string",
+            size: MAX_STRING_LENGTH + 1,
+            max: MAX_STRING_LENGTH,
+        });
+        let value = Tag::Compound(vec![(
+            "icon".to_string(),
+            Tag::String("a".repeat(MAX_STRING_LENGTH + 1)),
+        )]);
+        assert_eq!(encode_nbt("", &value), too_large);
+        let key = Tag::Compound(vec![("a".repeat(MAX_STRING_LENGTH + 1), Tag::Byte(0))]);
+        assert_eq!(encode_nbt("", &key), too_large);
+        // The limit counts encoded bytes, NUL takes two
+        let nul = Tag::Compound(vec![(
+            "icon".to_string(),
+            Tag::String("\0".repeat(MAX_STRING_LENGTH / 2 + 1)),
+        )]);
+        assert!(encode_nbt("", &nul).is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let deepest = nested(MAX_DEPTH);
+        assert_eq!(round_trip(&deepest), deepest);
+        let encoded = encode_nbt("", &nested(MAX_DEPTH + 1)).expect("Should encode");
+        assert_eq!(
+            decode_nbt(&encoded),
+            Err(DecodeError::TooLarge {
+                what: "NBT depth",
+                size: MAX_DEPTH + 1,
+                max: MAX_DEPTH,
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert_eq!(
+            decode_nbt(&[0x08, 0x00, 0x00]),
+            Err(DecodeError::InvalidTag(0x08))
+        );
+        assert!(matches!(
+            decode_nbt(&[0x0A, 0x00, 0x00]),
+            Err(DecodeError::UnexpectedEof { .. })
+        ));
+        assert_eq!(
+            decode_nbt(&[0x0A, 0x00, 0x00, 0x00, 0xFF]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+        assert_eq!(
+            decode_nbt(&[0x0A, 0x00, 0x00, 0x0D, 0x00, 0x00]),
+            Err(DecodeError::InvalidTag(0x0D))
+        );
+        // Negative and overlong array lengths fail before allocating
+        let negative = [
+            0x0A, 0x00, 0x00, 0x07, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00,
+        ];
+        assert_eq!(decode_nbt(&negative), Err(DecodeError::NegativeLength(-1)));
+        let overlong = [
+            0x0A, 0x00, 0x00, 0x0B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00,
+        ];
+        assert!(decode_nbt(&overlong).is_err());
+        // Only empty lists may have the End element type
+        let end_list = [
+            0x0A, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00,
+        ];
+        assert_eq!(
+            decode_nbt(&end_list),
+            Err(DecodeError::MissingField("NBT list type"))
+        );
+    }
+}
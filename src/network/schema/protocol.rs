@@ -0,0 +1,226 @@
+use anyhow::{Result, anyhow};
+
+/// Bit set on protocol numbers of Java snapshots, pre-releases and release candidates
+pub const SNAPSHOT_BIT: i32 = 0x40000000;
+
+/// A protocol number and the first and last release speaking it
+pub struct ProtocolEntry {
+    pub protocol: i32,
+    pub first: &'static str,
+    pub last: &'static str,
+}
+
+impl ProtocolEntry {
+    pub fn name(&self) -> String {
+        if self.first == self.last {
+            self.first.to_string()
+        } else {
+            format!("{}\u{2013}{}", self.first, self.last)
+        }
+    }
+
+    /// Whether a dotted release name falls between the first and last release
+    pub fn contains(&self, version: &str) -> bool {
+        match (
+            parse_version(self.first),
+            parse_version(self.last),
+            parse_version(version),
+        ) {
+            (Some(first), Some(last), Some(version)) => first <= version && version <= last,
+            _ => false,
+        }
+    }
+}
+
+const fn entry(protocol: i32, first: &'static str, last: &'static str) -> ProtocolEntry {
+    ProtocolEntry {
+        protocol,
+        first,
+        last,
+    }
+}
+
+/// Java Edition releases since the Netty rewrite
+pub const JAVA_PROTOCOLS: &[ProtocolEntry] = &[
+    entry(4, "1.7.2", "1.7.5"),
+    entry(5, "1.7.6", "1.7.10"),
+    entry(47, "1.8", "1.8.9"),
+    entry(107, "1.9", "1.9"),
+    entry(108, "1.9.1", "1.9.1"),
+    entry(109, "1.9.2", "1.9.2"),
+    entry(110, "1.9.3", "1.9.4"),
+    entry(210, "1.10", "1.10.2"),
+    entry(315, "1.11", "1.11"),
+    entry(316, "1.11.1", "1.11.2"),
+    entry(335, "1.12", "1.12"),
+    entry(338, "1.12.1", "1.12.1"),
+    entry(340, "1.12.2", "1.12.2"),
+    entry(393, "1.13", "1.13"),
+    entry(401, "1.13.1", "1.13.1"),
+    entry(404, "1.13.2", "1.13.2"),
+    entry(477, "1.14", "1.14"),
+    entry(480, "1.14.1", "1.14.1"),
+    entry(485, "1.14.2", "1.14.2"),
+    entry(490, "1.14.3", "1.14.3"),
+    entry(498, "1.14.4", "1.14.4"),
+    entry(573, "1.15", "1.15"),
+    entry(575, "1.15.1", "1.15.1"),
+    entry(578, "1.15.2", "1.15.2"),
+    entry(735, "1.16", "1.16"),
+    entry(736, "1.16.1", "1.16.1"),
+    entry(751, "1.16.2", "1.16.2"),
+    entry(753, "1.16.3", "1.16.3"),
+    entry(754, "1.16.4", "1.16.5"),
+    entry(755, "1.17", "1.17"),
+    entry(756, "1.17.1", "1.17.1"),
+    entry(757, "1.18", "1.18.1"),
+    entry(758, "1.18.2", "1.18.2"),
+    entry(759, "1.19", "1.19"),
+    entry(760, "1.19.1", "1.19.2"),
+    entry(761, "1.19.3", "1.19.3"),
+    entry(762, "1.19.4", "1.19.4"),
+    entry(763, "1.20", "1.20.1"),
+    entry(764, "1.20.2", "1.20.2"),
+    entry(765, "1.20.3", "1.20.4"),
+    entry(766, "1.20.5", "1.20.6"),
+    entry(767, "1.21", "1.21.1"),
+    entry(768, "1.21.2", "1.21.3"),
+    entry(769, "1.21.4", "1.21.4"),
+    entry(770, "1.21.5", "1.21.5"),
+    entry(771, "1.21.6", "1.21.6"),
+    entry(772, "1.21.7", "1.21.8"),
+    entry(773, "1.21.9", "1.21.10"),
+    entry(774, "1.21.11", "1.21.11"),
+];
+
+/// Java Edition snapshots, pre-releases and release candidates by `0x40000000` protocol,
+/// the scheme started with 1.16.4-pre1. Revisions not listed are still described by number.
+pub const JAVA_SNAPSHOT_PROTOCOLS: &[ProtocolEntry] = &[
+    entry(SNAPSHOT_BIT | 0x01, "1.16.4-pre1", "1.16.4-pre1"),
+    entry(SNAPSHOT_BIT | 0x02, "1.16.4-pre2", "1.16.4-pre2"),
+    entry(SNAPSHOT_BIT | 0x03, "1.16.4-rc1", "1.16.4-rc1"),
+];
+
+/// Bedrock Edition releases, hotfixes sharing a protocol are folded into ranges
+pub const BEDROCK_PROTOCOLS: &[ProtocolEntry] = &[
+    entry(407, "1.16.0", "1.16.10"),
+    entry(408, "1.16.20", "1.16.40"),
+    entry(419, "1.16.100", "1.16.101"),
+    entry(422, "1.16.200", "1.16.201"),
+    entry(428, "1.16.210", "1.16.210"),
+    entry(431, "1.16.220", "1.16.221"),
+    entry(440, "1.17.0", "1.17.2"),
+    entry(448, "1.17.10", "1.17.11"),
+    entry(465, "1.17.30", "1.17.34"),
+    entry(471, "1.17.40", "1.17.41"),
+    entry(475, "1.18.0", "1.18.2"),
+    entry(486, "1.18.10", "1.18.12"),
+    entry(503, "1.18.30", "1.18.33"),
+    entry(527, "1.19.0", "1.19.2"),
+    entry(534, "1.19.10", "1.19.11"),
+    entry(544, "1.19.20", "1.19.20"),
+    entry(545, "1.19.21", "1.19.22"),
+    entry(554, "1.19.30", "1.19.31"),
+    entry(557, "1.19.40", "1.19.41"),
+    entry(560, "1.19.50", "1.19.51"),
+    entry(567, "1.19.60", "1.19.62"),
+    entry(568, "1.19.63", "1.19.63"),
+    entry(575, "1.19.70", "1.19.73"),
+    entry(582, "1.19.80", "1.19.83"),
+    entry(589, "1.20.0", "1.20.1"),
+    entry(594, "1.20.10", "1.20.15"),
+    entry(618, "1.20.30", "1.20.32"),
+    entry(622, "1.20.40", "1.20.41"),
+    entry(630, "1.20.50", "1.20.51"),
+    entry(649, "1.20.60", "1.20.62"),
+    entry(662, "1.20.70", "1.20.73"),
+    entry(671, "1.20.80", "1.20.81"),
+    entry(685, "1.21.0", "1.21.1"),
+    entry(686, "1.21.2", "1.21.3"),
+    entry(712, "1.21.20", "1.21.23"),
+    entry(729, "1.21.30", "1.21.31"),
+    entry(748, "1.21.40", "1.21.44"),
+    entry(766, "1.21.50", "1.21.51"),
+    entry(776, "1.21.60", "1.21.62"),
+    entry(786, "1.21.70", "1.21.73"),
+    entry(800, "1.21.80", "1.21.84"),
+    entry(818, "1.21.90", "1.21.92"),
+    entry(819, "1.21.93", "1.21.94"),
+    entry(827, "1.21.100", "1.21.101"),
+    entry(844, "1.21.110", "1.21.114"),
+];
+
+/// Split a dotted release name into numbers, a missing patch counts as zero
+pub fn parse_version(version: &str) -> Option<[u32; 3]> {
+    let mut parts = [0; 3];
+    let mut count = 0;
+    for part in version.split('.') {
+        if count == 3 {
+            return None;
+        }
+        parts[count] = part.parse().ok()?;
+        count += 1;
+    }
+    if count < 2 { None } else { Some(parts) }
+}
+
+pub fn find_protocol(
+    table: &'static [ProtocolEntry],
+    protocol: i32,
+) -> Option<&'static ProtocolEntry> {
+    table.iter().find(|e| e.protocol == protocol)
+}
+
+pub fn find_release(
+    table: &'static [ProtocolEntry],
+    version: &str,
+) -> Option<&'static ProtocolEntry> {
+    table.iter().find(|e| e.contains(version))
+}
+
+/// Revision of a snapshot, pre-release or release candidate protocol
+pub fn snapshot_revision(protocol: i32) -> Option<i32> {
+    (protocol & SNAPSHOT_BIT != 0 && protocol > 0).then_some(protocol & !SNAPSHOT_BIT)
+}
+
+/// Describe a Java protocol number, snapshots missing from the table by their revision
+pub fn describe_java_protocol(protocol: i32) -> Option<String> {
+    if let Some(revision) = snapshot_revision(protocol) {
+        return Some(match find_protocol(JAVA_SNAPSHOT_PROTOCOLS, protocol) {
+            Some(entry) => entry.name(),
+            None => format!("snapshot revision {}", revision),
+        });
+    }
+    find_protocol(JAVA_PROTOCOLS, protocol).map(|e| e.name())
+}
+
+/// Accept a raw protocol number, a hexadecimal one like `0x40000100`, a snapshot revision
+/// like `snapshot-256`, a snapshot name like `1.16.4-pre1` or a release name like `1.20.4`
+pub fn parse_protocol(value: &str) -> Result<i32> {
+    let value = value.trim();
+    if let Some(revision) = value.strip_prefix("snapshot-") {
+        let revision = revision.parse::<i32>()?;
+        if !(1..SNAPSHOT_BIT).contains(&revision) {
+            return Err(anyhow!("Invalid snapshot revision: {}", revision));
+        }
+        return Ok(SNAPSHOT_BIT | revision);
+    }
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return Ok(u32::from_str_radix(hex, 16)? as i32);
+    }
+    if let Ok(protocol) = value.parse::<i32>() {
+        return Ok(protocol);
+    }
+    if let Some(entry) = JAVA_SNAPSHOT_PROTOCOLS
+        .iter()
+        .find(|e| e.first.eq_ignore_ascii_case(value))
+    {
+        return Ok(entry.protocol);
+    }
+    find_release(JAVA_PROTOCOLS, value)
+        .map(|e| e.protocol)
+        .ok_or(anyhow!("Unknown Java release or protocol: {}", value))
+}
@@ -1,6 +1,120 @@
+use crate::network::error::QueryError;
 use anyhow::{Result, anyhow};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::time::timeout;
+use clap::Args;
+use serde_json::{Value, json};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::{sleep, timeout};
+
+#[derive(Args, Debug)]
+pub struct TimeoutSettings {
+    /// Timeout for establishing connections, e.g. `5`, `2.5s` or `500ms`
+    #[arg(long, value_parser = parse_duration, default_value = "5s")]
+    pub connect_timeout: Duration,
+    /// Timeout for every response read from servers
+    #[arg(long, value_parser = parse_duration, default_value = "5s")]
+    pub read_timeout: Duration,
+    /// Overall time limit covering all addresses and modes
+    #[arg(long, value_parser = parse_duration)]
+    pub deadline: Option<Duration>,
+    /// Retry count for failed TCP connections, Bedrock pings are resent with `--bedrock-ping-interval` instead
+    #[arg(long, default_value = "0")]
+    pub retries: u32,
+    /// Initial delay between retries, doubled after each attempt
+    #[arg(long, value_parser = parse_duration, default_value = "500ms")]
+    pub retry_backoff: Duration,
+}
+
+/// Time spent in every phase of a query, phases not run stay `None`
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub status: Option<Duration>,
+    pub status_size: Option<usize>,
+    pub round_trip: Option<Duration>,
+}
+
+impl Timings {
+    pub fn add_dns(&mut self, time: Duration) {
+        self.dns = Some(self.dns.unwrap_or_default() + time);
+    }
+
+    pub fn to_json(&self) -> Value {
+        let millis = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+        json!({
+            "dns_ms": millis(self.dns),
+            "connect_ms": millis(self.connect),
+            "status_ms": millis(self.status),
+            "status_size": self.status_size,
+            "round_trip_ms": millis(self.round_trip),
+        })
+    }
+}
+
+struct TimeoutPolicy {
+    connect_timeout: Duration,
+    read_timeout: Duration,
+    deadline: Option<Instant>,
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+static TIMEOUT_POLICY: OnceLock<TimeoutPolicy> = OnceLock::new();
+
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        (value, 1.0)
+    };
+    let secs = number.trim().parse::<f64>()? * scale;
+    Duration::try_from_secs_f64(secs).map_err(|e| anyhow!("Invalid duration {}: {}", value, e))
+}
+
+pub fn setup_timeouts(settings: &TimeoutSettings) {
+    let policy = TimeoutPolicy {
+        connect_timeout: settings.connect_timeout,
+        read_timeout: settings.read_timeout,
+        deadline: settings.deadline.map(|d| Instant::now() + d),
+        retries: settings.retries,
+        retry_backoff: settings.retry_backoff,
+    };
+    if TIMEOUT_POLICY.set(policy).is_err() {
+        log::warn!("Timeout policy is already set");
+    }
+}
+
+fn policy() -> &'static TimeoutPolicy {
+    TIMEOUT_POLICY.get_or_init(|| TimeoutPolicy {
+        connect_timeout: Duration::from_secs(5),
+        read_timeout: Duration::from_secs(5),
+        deadline: None,
+        retries: 0,
+        retry_backoff: Duration::from_millis(500),
+    })
+}
+
+fn remaining() -> Option<Duration> {
+    policy()
+        .deadline
+        .map(|d| d.saturating_duration_since(Instant::now()))
+}
+
+fn bounded(time: Duration) -> Duration {
+    remaining().map_or(time, |left| left.min(time))
+}
+
+pub fn connect_timeout() -> Duration {
+    bounded(policy().connect_timeout)
+}
+
+pub fn read_timeout() -> Duration {
+    bounded(policy().read_timeout)
+}
 
 pub fn now_timestamp() -> i64 {
     SystemTime::now()
@@ -9,6 +123,16 @@ pub fn now_timestamp() -> i64 {
         .unwrap_or(0)
 }
 
+/// Clock difference of a server reporting its own time instead of echoing ours,
+/// `None` if the value does not look like a wall clock timestamp
+pub fn clock_skew(sent: i64, round_trip: Duration, server_clock: i64) -> Option<i64> {
+    const PLAUSIBLE_SKEW: u64 = 24 * 60 * 60 * 1000;
+    // The server clock comes off the wire, so any value has to be handled without overflowing
+    let midpoint = sent.checked_add(i64::try_from(round_trip.as_millis() / 2).ok()?)?;
+    let skew = server_clock.checked_sub(midpoint)?;
+    (skew.unsigned_abs() < PLAUSIBLE_SKEW).then_some(skew)
+}
+
 pub async fn generic_timeout<F, O>(
     time: Duration,
     future: F,
@@ -17,11 +141,14 @@ pub async fn generic_timeout<F, O>(
 where
     F: Future<Output = Result<O>>,
 {
-    Ok(timeout(time, future)
+    timeout(time, future)
         .await
-        .map_err(|_| anyhow!("{} timed out", timeout_message.to_string()))??)
+        .map_err(|_| QueryError::Timeout {
+            phase: timeout_message.to_string(),
+        })?
 }
 
+#[cfg_attr(not(feature = "ping-legacy"), allow(dead_code))]
 pub async fn io_timeout<F, O>(
     time: Duration,
     future: F,
@@ -32,5 +159,39 @@ where
 {
     Ok(timeout(time, future)
         .await
-        .map_err(|_| anyhow!("{} timed out", timeout_message.to_string()))??)
+        .map_err(|_| QueryError::Timeout {
+            phase: timeout_message.to_string(),
+        })??)
+}
+
+pub async fn deadline_timeout<F, O>(future: F) -> Result<O>
+where
+    F: Future<Output = Result<O>>,
+{
+    match remaining() {
+        Some(left) => generic_timeout(left, future, "Deadline").await,
+        None => future.await,
+    }
+}
+
+pub async fn with_retries<F, Fut, O>(name: impl ToString, mut attempt: F) -> Result<O>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<O>>,
+{
+    let name = name.to_string();
+    let mut backoff = policy().retry_backoff;
+    let mut tries = 0;
+    loop {
+        match attempt(tries).await {
+            Ok(res) => return Ok(res),
+            Err(e) if tries < policy().retries && remaining().is_none_or(|left| left > backoff) => {
+                tries += 1;
+                log::debug!("{} failed, retry {} in {:?}: {}", name, tries, backoff, e);
+                sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
@@ -0,0 +1,55 @@
+//! Query helpers shared by the commands running over several servers.
+
+use crate::analyze::StatusPayload;
+use crate::history;
+use crate::mode::{QueryEngine, QueryMode};
+use crate::network::error::{QueryError, failure_kind};
+use crate::network::util::deadline_timeout;
+use anyhow::{Result, anyhow};
+
+/// Server list entries queried at the same time
+pub const SERVER_LIST_CONCURRENCY: usize = 8;
+
+/// One line per address tried, a single attempt is already spelled out by the error itself
+pub fn failure_table(e: &anyhow::Error) {
+    let Some(QueryError::AllAttemptsFailed { attempts, .. }) = e.downcast_ref::<QueryError>()
+    else {
+        return;
+    };
+    if attempts.len() < 2 {
+        return;
+    }
+    log::error!("  {:32} {:16} Cause", "Address", "Failure");
+    for attempt in attempts {
+        let kind = failure_kind(&attempt.error).to_string();
+        log::error!("  {:32} {:16} {}", attempt.target, kind, attempt.cause());
+    }
+}
+
+/// Query with the overall deadline, recording the outcome when `--record` is set
+pub async fn query_mode(
+    engine: &QueryEngine<'_>,
+    mode: QueryMode,
+    address: &str,
+) -> Result<StatusPayload> {
+    let result = deadline_timeout(engine.query(mode, address)).await;
+    history::record(address, mode, &result).await;
+    result
+}
+
+/// Try the modes one after another, returning the first answer or the last failure
+pub async fn query_modes(
+    engine: &QueryEngine<'_>,
+    modes: &[QueryMode],
+    address: &str,
+) -> (QueryMode, Result<StatusPayload>) {
+    let mut last = (modes[0], Err(anyhow!("No query mode selected")));
+    for &mode in modes {
+        let result = query_mode(engine, mode, address).await;
+        if result.is_ok() {
+            return (mode, result);
+        }
+        last = (mode, result);
+    }
+    last
+}